@@ -0,0 +1 @@
+DROP TABLE scrape_run;
\ No newline at end of file
@@ -0,0 +1,14 @@
+CREATE TABLE scrape_run (
+	id INTEGER PRIMARY KEY NOT NULL,
+	loc INTEGER NOT NULL,
+	run_status INTEGER NOT NULL,
+	started_at TIMESTAMP NOT NULL,
+	finished_at TIMESTAMP,
+	pages_fetched INTEGER NOT NULL DEFAULT 0,
+	rows_parsed INTEGER NOT NULL DEFAULT 0,
+	rows_inserted INTEGER NOT NULL DEFAULT 0,
+	classifications_added INTEGER NOT NULL DEFAULT 0,
+	error_count INTEGER NOT NULL DEFAULT 0,
+	errors TEXT,
+	watermark TIMESTAMP
+);
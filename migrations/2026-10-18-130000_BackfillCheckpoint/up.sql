@@ -0,0 +1,12 @@
+CREATE TABLE backfill_checkpoint (
+	id INTEGER PRIMARY KEY NOT NULL,
+	loc INTEGER NOT NULL,
+	base_url TEXT NOT NULL,
+	page INTEGER NOT NULL DEFAULT 0,
+	next_url_part TEXT,
+	pages_fetched INTEGER NOT NULL DEFAULT 0,
+	rows_inserted INTEGER NOT NULL DEFAULT 0,
+	completed BOOLEAN NOT NULL DEFAULT 0,
+	updated_at TIMESTAMP NOT NULL,
+	UNIQUE (loc, base_url)
+);
@@ -0,0 +1,6 @@
+CREATE TABLE source_lock (
+	loc INTEGER PRIMARY KEY NOT NULL,
+	holder TEXT NOT NULL,
+	acquired_at TIMESTAMP NOT NULL,
+	expires_at TIMESTAMP NOT NULL
+);
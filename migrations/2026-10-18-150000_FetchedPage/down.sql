@@ -0,0 +1 @@
+DROP TABLE fetched_page;
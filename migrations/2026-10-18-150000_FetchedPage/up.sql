@@ -0,0 +1,16 @@
+CREATE TABLE fetched_page (
+	id INTEGER PRIMARY KEY NOT NULL,
+	run_id INTEGER,
+	loc INTEGER NOT NULL,
+	url TEXT NOT NULL,
+	method TEXT NOT NULL,
+	request_headers TEXT NOT NULL,
+	status INTEGER NOT NULL,
+	response_headers TEXT NOT NULL,
+	fetched_at TIMESTAMP NOT NULL,
+	content_hash TEXT NOT NULL,
+	size INTEGER NOT NULL
+);
+
+CREATE INDEX fetched_page_run_id ON fetched_page (run_id);
+CREATE INDEX fetched_page_content_hash ON fetched_page (content_hash);
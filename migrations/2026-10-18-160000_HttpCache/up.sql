@@ -0,0 +1,8 @@
+CREATE TABLE http_cache (
+	loc INTEGER NOT NULL,
+	url TEXT NOT NULL,
+	etag TEXT,
+	last_modified TEXT,
+	updated_at TIMESTAMP NOT NULL,
+	PRIMARY KEY (loc, url)
+);
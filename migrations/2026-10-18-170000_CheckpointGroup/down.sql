@@ -0,0 +1 @@
+ALTER TABLE backfill_checkpoint DROP COLUMN group_name;
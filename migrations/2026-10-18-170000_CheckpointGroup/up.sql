@@ -0,0 +1 @@
+ALTER TABLE backfill_checkpoint ADD COLUMN group_name TEXT;
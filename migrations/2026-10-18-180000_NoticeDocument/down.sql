@@ -0,0 +1 @@
+DROP TABLE notice_document;
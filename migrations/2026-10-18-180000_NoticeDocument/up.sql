@@ -0,0 +1,16 @@
+CREATE TABLE notice_document (
+	id INTEGER PRIMARY KEY NOT NULL,
+	breach_data_id INTEGER NOT NULL UNIQUE REFERENCES breach_data (id),
+	link TEXT NOT NULL,
+	content_hash TEXT,
+	mime_type TEXT,
+	size INTEGER,
+	fetched_at TIMESTAMP,
+	status INTEGER,
+	error TEXT,
+	attempts INTEGER NOT NULL DEFAULT 0,
+	attempted_at TIMESTAMP NOT NULL,
+	retry_at TIMESTAMP
+);
+
+CREATE INDEX notice_document_content_hash ON notice_document (content_hash);
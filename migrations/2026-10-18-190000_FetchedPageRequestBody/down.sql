@@ -0,0 +1 @@
+ALTER TABLE fetched_page DROP COLUMN request_body_hash;
@@ -0,0 +1 @@
+ALTER TABLE fetched_page ADD COLUMN request_body_hash TEXT;
@@ -0,0 +1 @@
+ALTER TABLE scrape_run DROP COLUMN error_kind;
@@ -0,0 +1 @@
+ALTER TABLE scrape_run ADD COLUMN error_kind TEXT;
@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::dto::{Breach, State};
+
+#[derive(Debug, Parser)]
+#[command(name = "breach-tracker", about = "Tracks data breaches reported to governments")]
+pub struct Cli {
+	/// File describing the urls, headers and pagination of every source
+	#[arg(long, global = true, env = "SOURCES_FILE", default_value = "sources.toml")]
+	pub sources: PathBuf,
+	/// Directory every fetched response body is archived in
+	#[arg(long, global = true, env = "ARCHIVE_DIR", default_value = "archive")]
+	pub archive_dir: PathBuf,
+	/// Do not archive fetched response bodies
+	#[arg(long, global = true)]
+	pub no_archive: bool,
+	/// WARC file every fetched request and response is appended to, gzip compressed when it ends in .gz
+	#[arg(long, global = true, env = "WARC_OUTPUT")]
+	pub warc_output: Option<PathBuf>,
+	#[command(subcommand)]
+	pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+	/// Retrieves new breaches from the given states and stores them
+	Scrape {
+		/// Comma separated list of states to scrape, all states when omitted
+		#[arg(long, value_delimiter = ',', ignore_case = true)]
+		state: Vec<State>,
+		/// Maximum number of sources retrieved at the same time
+		#[arg(long, default_value_t = 4)]
+		concurrency: usize,
+		/// Retrieve and parse without writing, printing what would change instead
+		#[arg(long)]
+		dry_run: bool,
+		/// Format of the dry run report
+		#[arg(long, value_enum, default_value_t = ReportFormat::Text, requires = "dry_run")]
+		report: ReportFormat,
+		/// Download the notice letters of the scraped states afterwards, like the documents command
+		#[arg(long, conflicts_with = "dry_run")]
+		documents: bool,
+	},
+	/// Retrieves every breach back to the initial date of the given states, storing progress after every page
+	Backfill {
+		/// Comma separated list of states to backfill, all states when omitted
+		#[arg(long, value_delimiter = ',', ignore_case = true)]
+		state: Vec<State>,
+		/// Maximum number of sources retrieved at the same time
+		#[arg(long, default_value_t = 4)]
+		concurrency: usize,
+		/// Discard the checkpoints of earlier backfills and start again from the first page
+		#[arg(long)]
+		restart: bool,
+	},
+	/// Parses stored copies of the pages of the given states instead of fetching them and stores the breaches they contain
+	Replay {
+		/// Comma separated list of states to replay, all states when omitted
+		#[arg(long, value_delimiter = ',', ignore_case = true)]
+		state: Vec<State>,
+		/// Maximum number of sources replayed at the same time
+		#[arg(long, default_value_t = 4)]
+		concurrency: usize,
+		/// Directory of saved pages named after their url, the archive when omitted
+		#[arg(long)]
+		dir: Option<PathBuf>,
+		/// WARC file to replay the responses of, the archive when omitted
+		#[arg(long, conflicts_with = "dir")]
+		warc: Option<PathBuf>,
+		/// Only replay pages archived by this scrape run, the latest archived copy of every page when omitted
+		#[arg(long, conflicts_with_all = ["dir", "warc"])]
+		run: Option<i32>,
+		/// Parse without writing, printing what would change instead
+		#[arg(long)]
+		dry_run: bool,
+		/// Format of the dry run report
+		#[arg(long, value_enum, default_value_t = ReportFormat::Text, requires = "dry_run")]
+		report: ReportFormat,
+	},
+	/// Downloads the notice letters stored breaches of the given states link to, into the archive
+	Documents {
+		/// Comma separated list of states, all enabled states when omitted
+		#[arg(long, value_delimiter = ',', ignore_case = true)]
+		state: Vec<State>,
+		/// Maximum number of letters downloaded at the same time
+		#[arg(long, default_value_t = 4)]
+		concurrency: usize,
+	},
+	/// Keeps running, scraping every source with a schedule in the sources file whenever it is due
+	Daemon {
+		/// Comma separated list of states to schedule, all scheduled states when omitted
+		#[arg(long, value_delimiter = ',', ignore_case = true)]
+		state: Vec<State>,
+		/// Maximum number of sources retrieved at the same time
+		#[arg(long, default_value_t = 4)]
+		concurrency: usize,
+	},
+	/// Prints stored breaches matching the given filters
+	Query {
+		#[command(flatten)]
+		filter: BreachFilter,
+	},
+	/// Writes stored breaches matching the given filters to a file or stdout
+	Export {
+		#[command(flatten)]
+		filter: BreachFilter,
+		#[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+		format: ExportFormat,
+		/// File to write to, stdout when omitted
+		#[arg(long, short)]
+		output: Option<PathBuf>,
+	},
+	/// Prints the last retrieved date, last successful run and last failure of every state
+	Status,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct BreachFilter {
+	/// Comma separated list of states, all states when omitted
+	#[arg(long, value_delimiter = ',', ignore_case = true)]
+	pub state: Vec<State>,
+	/// Only breaches reported on or after this date (YYYY-MM-DD)
+	#[arg(long)]
+	pub since: Option<NaiveDate>,
+	/// Only breaches reported on or before this date (YYYY-MM-DD)
+	#[arg(long)]
+	pub until: Option<NaiveDate>,
+	/// Only breaches whose organization name contains this text (case insensitive)
+	#[arg(long)]
+	pub organization: Option<String>,
+	#[arg(long)]
+	pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+	Json,
+	Csv,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+	Text,
+	Json,
+}
+
+impl BreachFilter {
+	pub fn matches(&self, breach: &Breach) -> bool {
+		if !self.state.is_empty() && !self.state.contains(&breach.loc) {
+			return false;
+		}
+
+		if let Some(since) = self.since {
+			if breach.date_reported.date() < since {
+				return false;
+			}
+		}
+
+		if let Some(until) = self.until {
+			if breach.date_reported.date() > until {
+				return false;
+			}
+		}
+
+		if let Some(organization) = &self.organization {
+			if !breach.organization_name.to_lowercase().contains(&organization.to_lowercase()) {
+				return false;
+			}
+		}
+
+		true
+	}
+}
@@ -0,0 +1,521 @@
+use std::{collections::{BTreeMap, HashMap}, fs, path::{Path, PathBuf}};
+
+use reqwest::{Url, header::{HeaderMap, HeaderName, HeaderValue}};
+use serde::Deserialize;
+
+use crate::{dto::State, error::{Error, Result}, retrievers::{RetryPolicy, WebRequestType, body::{BodyContent, RequestBody}, pagination::{DEFAULT_MAX_PAGES, Pagination, PaginationKind}, session::SessionConfig, sharepoint::SharePointConfig, client::HttpConfig, politeness::{PolitenessPolicy, RateLimit}, tls::{TlsBackend, TlsConfig}}, schedule::{RawSchedule, Schedule}, sources::{IncrementalPolicy, default_headers}};
+
+pub const GROUP_PLACEHOLDER: &str = "{group}";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+	#[serde(default, rename = "source")]
+	sources: Vec<RawSource>,
+	politeness: Option<RawPoliteness>,
+	http: Option<RawHttp>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawHttp {
+	connect_timeout: Option<String>,
+	read_timeout: Option<String>,
+	timeout: Option<String>,
+	max_response_size: Option<u64>,
+	user_agent: Option<String>,
+	proxy: Option<String>,
+	pool_idle_timeout: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPoliteness {
+	min_delay: Option<String>,
+	burst: Option<u32>,
+	#[serde(default)]
+	robots: bool,
+	#[serde(default)]
+	hosts: BTreeMap<String, RawRateLimit>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRateLimit {
+	min_delay: Option<String>,
+	burst: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSource {
+	state: State,
+	#[serde(default = "enabled_by_default")]
+	enabled: bool,
+	base_url: String,
+	#[serde(default)]
+	groups: Vec<String>,
+	#[serde(default)]
+	request_type: RawRequestType,
+	#[serde(default)]
+	headers: BTreeMap<String, String>,
+	pagination: Option<RawPagination>,
+	schedule: Option<RawSchedule>,
+	retry: Option<RawRetry>,
+	incremental: Option<RawIncremental>,
+	session: Option<RawSession>,
+	sharepoint: Option<RawSharePoint>,
+	tls: Option<RawTls>,
+	body: Option<RawBody>,
+}
+
+/// Body of a POST source, exactly one of `form`, `json` or `raw`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawBody {
+	form: Option<BTreeMap<String, String>>,
+	json: Option<serde_json::Value>,
+	raw: Option<String>,
+	content_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPagination {
+	#[serde(default)]
+	kind: RawPaginationKind,
+	start: Option<i32>,
+	step: Option<i32>,
+	limit: Option<i32>,
+	#[serde(default)]
+	bare_first_page: bool,
+	max_pages: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RawPaginationKind {
+	#[default]
+	PageNumber,
+	Offset,
+	NextLink,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTls {
+	backend: Option<RawTlsBackend>,
+	min_version: Option<String>,
+	#[serde(default)]
+	ca_roots: Vec<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RawTlsBackend {
+	Native,
+	Rustls,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRetry {
+	max_retries: Option<u32>,
+	initial_backoff: Option<String>,
+	max_backoff: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawIncremental {
+	overlap: Option<String>,
+	stop_after_known: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSession {
+	start_url: Option<String>,
+	#[serde(default)]
+	fields: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSharePoint {
+	groups_url: String,
+	group_field: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum RawRequestType {
+	#[default]
+	Get,
+	Post,
+}
+
+fn enabled_by_default() -> bool {
+	true
+}
+
+#[derive(Debug)]
+pub struct Config {
+	pub sources: Vec<SourceConfig>,
+	/// Rate limits and robots.txt handling shared by every source
+	pub politeness: PolitenessPolicy,
+	/// Timeouts, limits and identity shared by the http clients of every source
+	pub http: HttpConfig,
+}
+
+/// A validated `[[source]]` entry, with every group expanded into its own url
+#[derive(Debug, Clone)]
+pub struct SourceConfig {
+	pub state: State,
+	pub enabled: bool,
+	pub urls: Vec<String>,
+	pub headers: HeaderMap<HeaderValue>,
+	pub request_type: WebRequestType,
+	pub pagination: Option<Pagination>,
+	/// When the daemon runs this source, never when not set
+	pub schedule: Option<Schedule>,
+	pub retry: RetryPolicy,
+	pub incremental: IncrementalPolicy,
+	/// Cookies and hidden form fields carried between the pages of multi page sources
+	pub session: Option<SessionConfig>,
+	/// Groups of a SharePoint list view discovered on every retrieval, `urls` then holds the one base url with its
+	/// group placeholder
+	pub sharepoint: Option<SharePointConfig>,
+	pub tls: TlsConfig,
+}
+
+impl Config {
+	pub fn load(path: &Path) -> Result<Config> {
+		let text = fs::read_to_string(path).map_err(|source| Error::Io { context: format!("could not read sources file {}", path.display()), source })?;
+
+		let mut config = Config::parse(&text).map_err(|err| match err {
+			Error::Config { message, .. } => Error::Config { path: Some(path.to_path_buf()), message },
+			err => err,
+		})?;
+
+		// CA roots are relative to the sources file, not to wherever the binary happens to run
+		let dir = path.parent().unwrap_or(Path::new(""));
+		for source in config.sources.iter_mut() {
+			source.tls.ca_roots = source.tls.ca_roots.iter().map(|root| dir.join(root)).collect();
+		}
+
+		Ok(config)
+	}
+
+	pub fn parse(text: &str) -> Result<Config> {
+		let raw: RawConfig = toml::from_str(text).map_err(|err| Error::config(err.to_string()))?;
+
+		let mut sources: Vec<SourceConfig> = vec!();
+		for source in raw.sources {
+			let state = source.state;
+			if sources.iter().any(|s| s.state == state) {
+				return Err(Error::config(format!("{:?} is configured more than once", state)));
+			}
+
+			sources.push(SourceConfig::validate(source).map_err(|err| Error::config(format!("{:?}: {}", state, err)))?);
+		}
+
+		let politeness = match &raw.politeness {
+			Some(politeness) => parse_politeness(politeness).map_err(|err| Error::config(format!("politeness: {}", err)))?,
+			None => PolitenessPolicy::default(),
+		};
+
+		let http = match &raw.http {
+			Some(http) => parse_http(http).map_err(|err| Error::config(format!("http: {}", err)))?,
+			None => HttpConfig::default(),
+		};
+
+		Ok(Config { sources, politeness, http })
+	}
+
+	pub fn source(&self, state: State) -> Option<&SourceConfig> {
+		self.sources.iter().find(|source| source.state == state)
+	}
+}
+
+impl SourceConfig {
+	fn validate(raw: RawSource) -> std::result::Result<SourceConfig, String> {
+		let has_placeholder = raw.base_url.contains(GROUP_PLACEHOLDER);
+		if raw.sharepoint.is_some() {
+			if !has_placeholder {
+				return Err(format!("sharepoint sources need {} in base_url", GROUP_PLACEHOLDER));
+			}
+			if !raw.groups.is_empty() {
+				return Err("sharepoint sources discover their groups, remove groups".to_string());
+			}
+		}
+		else if has_placeholder && raw.groups.is_empty() {
+			return Err(format!("base_url contains {} but no groups are configured", GROUP_PLACEHOLDER));
+		}
+		if !has_placeholder && !raw.groups.is_empty() {
+			return Err(format!("groups are configured but base_url does not contain {}", GROUP_PLACEHOLDER));
+		}
+
+		let urls = if has_placeholder && raw.sharepoint.is_none() {
+			raw.groups.iter().map(|group| raw.base_url.replace(GROUP_PLACEHOLDER, group)).collect()
+		}
+		else {
+			vec!(raw.base_url)
+		};
+
+		for url in urls.iter() {
+			Url::parse(&url.replace(GROUP_PLACEHOLDER, "")).map_err(|err| format!("invalid url {}: {}", url, err))?;
+		}
+
+		let mut headers = default_headers();
+		for (name, value) in raw.headers.iter() {
+			let header_name = HeaderName::from_bytes(name.as_bytes()).map_err(|err| format!("invalid header name {}: {}", name, err))?;
+			let header_value = HeaderValue::from_str(value).map_err(|err| format!("invalid value for header {}: {}", name, err))?;
+			headers.insert(header_name, header_value);
+		}
+
+		let pagination = match &raw.pagination {
+			Some(pagination) => Some(parse_pagination(pagination).map_err(|err| format!("pagination: {}", err))?),
+			None => None,
+		};
+
+		let sharepoint = match &raw.sharepoint {
+			Some(sharepoint) => Some(parse_sharepoint(sharepoint).map_err(|err| format!("sharepoint: {}", err))?),
+			None => None,
+		};
+
+		// The pages of every group are linked by their NextHref
+		let pagination = match (pagination, &sharepoint) {
+			(Some(Pagination { kind: PaginationKind::PageNumber { .. } | PaginationKind::Offset { .. }, .. }), Some(_)) => {
+				return Err("sharepoint sources need pagination kind next_link".to_string());
+			},
+			(None, Some(_)) => Some(Pagination { kind: PaginationKind::NextLink, max_pages: DEFAULT_MAX_PAGES }),
+			(pagination, _) => pagination,
+		};
+
+		let schedule = match &raw.schedule {
+			Some(schedule) => Some(Schedule::parse(schedule)?),
+			None => None,
+		};
+
+		let mut retry = RetryPolicy::default();
+		if let Some(raw_retry) = &raw.retry {
+			if let Some(max_retries) = raw_retry.max_retries {
+				retry.max_retries = max_retries;
+			}
+			if let Some(initial_backoff) = &raw_retry.initial_backoff {
+				retry.initial_backoff = humantime::parse_duration(initial_backoff).map_err(|err| format!("invalid initial_backoff {}: {}", initial_backoff, err))?;
+			}
+			if let Some(max_backoff) = &raw_retry.max_backoff {
+				retry.max_backoff = humantime::parse_duration(max_backoff).map_err(|err| format!("invalid max_backoff {}: {}", max_backoff, err))?;
+			}
+			if retry.initial_backoff > retry.max_backoff {
+				return Err(format!("initial_backoff {:?} is longer than max_backoff {:?}", retry.initial_backoff, retry.max_backoff));
+			}
+		}
+
+		let mut incremental = IncrementalPolicy::default();
+		if let Some(raw_incremental) = &raw.incremental {
+			if let Some(overlap) = &raw_incremental.overlap {
+				incremental.overlap = humantime::parse_duration(overlap).map_err(|err| format!("invalid overlap {}: {}", overlap, err))?;
+			}
+			if raw_incremental.stop_after_known == Some(0) {
+				return Err("stop_after_known must be positive".to_string());
+			}
+			incremental.stop_after_known = raw_incremental.stop_after_known;
+		}
+
+		let session = match &raw.session {
+			Some(session) => {
+				if let Some(start_url) = &session.start_url {
+					Url::parse(start_url).map_err(|err| format!("invalid session start_url {}: {}", start_url, err))?;
+				}
+
+				Some(SessionConfig { start_url: session.start_url.clone(), fields: session.fields.clone().into_iter().collect() })
+			},
+			None => None,
+		};
+
+		let tls = match &raw.tls {
+			Some(tls) => parse_tls(tls).map_err(|err| format!("tls: {}", err))?,
+			None => TlsConfig::default(),
+		};
+
+		let request_type = match (raw.request_type, &raw.body) {
+			(RawRequestType::Get, None) => WebRequestType::Get,
+			(RawRequestType::Get, Some(_)) => return Err("a body needs request_type POST".to_string()),
+			(RawRequestType::Post, body) => WebRequestType::Post(body.as_ref().map(parse_body).transpose().map_err(|err| format!("body: {}", err))?),
+		};
+
+		Ok(SourceConfig {
+			state: raw.state,
+			enabled: raw.enabled,
+			urls,
+			headers,
+			request_type,
+			pagination,
+			schedule,
+			retry,
+			incremental,
+			session,
+			sharepoint,
+			tls,
+		})
+	}
+}
+
+fn parse_http(raw: &RawHttp) -> std::result::Result<HttpConfig, String> {
+	let mut http = HttpConfig::default();
+	let duration = |name: &str, value: &String| humantime::parse_duration(value).map_err(|err| format!("invalid {} {}: {}", name, value, err));
+
+	if let Some(connect_timeout) = &raw.connect_timeout {
+		http.connect_timeout = duration("connect_timeout", connect_timeout)?;
+	}
+	if let Some(read_timeout) = &raw.read_timeout {
+		http.read_timeout = duration("read_timeout", read_timeout)?;
+	}
+	if let Some(timeout) = &raw.timeout {
+		http.timeout = Some(duration("timeout", timeout)?);
+	}
+	if let Some(pool_idle_timeout) = &raw.pool_idle_timeout {
+		http.pool_idle_timeout = duration("pool_idle_timeout", pool_idle_timeout)?;
+	}
+	if let Some(max_response_size) = raw.max_response_size {
+		if max_response_size == 0 {
+			return Err("max_response_size must be positive".to_string());
+		}
+		http.max_response_size = max_response_size;
+	}
+	if let Some(user_agent) = &raw.user_agent {
+		HeaderValue::from_str(user_agent).map_err(|err| format!("invalid user_agent {}: {}", user_agent, err))?;
+		http.user_agent = user_agent.clone();
+	}
+	if let Some(proxy) = &raw.proxy {
+		reqwest::Proxy::all(proxy).map_err(|err| format!("invalid proxy {}: {}", proxy, err))?;
+		http.proxy = Some(proxy.clone());
+	}
+
+	Ok(http)
+}
+
+fn parse_pagination(raw: &RawPagination) -> std::result::Result<Pagination, String> {
+	let kind = match raw.kind {
+		RawPaginationKind::PageNumber => {
+			if raw.limit.is_some() {
+				return Err("limit only applies to offset pagination".to_string());
+			}
+
+			let step = raw.step.unwrap_or(1);
+			if step <= 0 {
+				return Err(format!("step must be positive, got {}", step));
+			}
+
+			PaginationKind::PageNumber { start: raw.start.unwrap_or(0), step, bare_first_page: raw.bare_first_page }
+		},
+		RawPaginationKind::Offset => {
+			if raw.start.is_some() || raw.step.is_some() {
+				return Err("offset pagination starts at 0 and steps by limit, it takes no start or step".to_string());
+			}
+
+			let limit = raw.limit.ok_or("offset pagination needs a limit")?;
+			if limit <= 0 {
+				return Err(format!("limit must be positive, got {}", limit));
+			}
+
+			PaginationKind::Offset { limit, bare_first_page: raw.bare_first_page }
+		},
+		RawPaginationKind::NextLink => {
+			if raw.start.is_some() || raw.step.is_some() || raw.limit.is_some() || raw.bare_first_page {
+				return Err("next_link pagination always requests the base url first and takes no start, step, limit or bare_first_page".to_string());
+			}
+
+			PaginationKind::NextLink
+		},
+	};
+
+	let max_pages = raw.max_pages.unwrap_or(DEFAULT_MAX_PAGES);
+	if max_pages == 0 {
+		return Err("max_pages must be positive".to_string());
+	}
+
+	Ok(Pagination { kind, max_pages })
+}
+
+fn parse_sharepoint(raw: &RawSharePoint) -> std::result::Result<SharePointConfig, String> {
+	Url::parse(&raw.groups_url).map_err(|err| format!("invalid groups_url {}: {}", raw.groups_url, err))?;
+	if raw.group_field.is_empty() {
+		return Err("group_field is empty".to_string());
+	}
+
+	Ok(SharePointConfig { groups_url: raw.groups_url.clone(), group_field: raw.group_field.clone() })
+}
+
+fn parse_tls(raw: &RawTls) -> std::result::Result<TlsConfig, String> {
+	let backend = match raw.backend {
+		Some(RawTlsBackend::Native) | None => TlsBackend::Native,
+		Some(RawTlsBackend::Rustls) => TlsBackend::Rustls,
+	};
+
+	let min_version = match raw.min_version.as_deref() {
+		None => None,
+		Some("1.0") => Some(reqwest::tls::Version::TLS_1_0),
+		Some("1.1") => Some(reqwest::tls::Version::TLS_1_1),
+		Some("1.2") => Some(reqwest::tls::Version::TLS_1_2),
+		Some("1.3") => Some(reqwest::tls::Version::TLS_1_3),
+		Some(version) => return Err(format!("invalid min_version {}, expected one of 1.0, 1.1, 1.2 or 1.3", version)),
+	};
+
+	if backend == TlsBackend::Native && min_version == Some(reqwest::tls::Version::TLS_1_3) {
+		return Err("the native backend cannot require TLS 1.3, use the rustls backend".to_string());
+	}
+
+	Ok(TlsConfig { backend, min_version, ca_roots: raw.ca_roots.clone() })
+}
+
+fn parse_body(raw: &RawBody) -> std::result::Result<RequestBody, String> {
+	let content = match (&raw.form, &raw.json, &raw.raw) {
+		(Some(form), None, None) => BodyContent::Form(form.iter().map(|(name, value)| (name.clone(), value.clone())).collect()),
+		(None, Some(json), None) => BodyContent::Json(json.clone()),
+		(None, None, Some(raw)) => BodyContent::Raw(raw.clone()),
+		_ => return Err("expected exactly one of form, json or raw".to_string()),
+	};
+
+	let content_type = match &raw.content_type {
+		Some(content_type) => {
+			HeaderValue::from_str(content_type).map_err(|err| format!("invalid content_type {}: {}", content_type, err))?;
+			content_type.clone()
+		},
+		None => content.default_content_type().to_string(),
+	};
+
+	Ok(RequestBody { content_type, content })
+}
+
+fn parse_politeness(raw: &RawPoliteness) -> std::result::Result<PolitenessPolicy, String> {
+	let rate_limit = parse_rate_limit(&RateLimit::default(), &raw.min_delay, raw.burst)?;
+
+	let mut hosts = HashMap::new();
+	for (host, limit) in raw.hosts.iter() {
+		hosts.insert(host.to_lowercase(), parse_rate_limit(&rate_limit, &limit.min_delay, limit.burst).map_err(|err| format!("{}: {}", host, err))?);
+	}
+
+	Ok(PolitenessPolicy { rate_limit, hosts, robots: raw.robots })
+}
+
+/// Rate limit with the given fields replacing those of `base`
+fn parse_rate_limit(base: &RateLimit, min_delay: &Option<String>, burst: Option<u32>) -> std::result::Result<RateLimit, String> {
+	let mut rate_limit = base.clone();
+
+	if let Some(min_delay) = min_delay {
+		rate_limit.min_delay = humantime::parse_duration(min_delay).map_err(|err| format!("invalid min_delay {}: {}", min_delay, err))?;
+	}
+
+	if let Some(burst) = burst {
+		if burst == 0 {
+			return Err("burst must be at least 1".to_string());
+		}
+		rate_limit.burst = burst;
+	}
+
+	Ok(rate_limit)
+}
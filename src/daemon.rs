@@ -0,0 +1,159 @@
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use diesel::SqliteConnection;
+use futures::{StreamExt, stream::FuturesUnordered};
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::{config::Config, data::{establish_connection, get_last_scrape_run}, dto::State, error::{Error, Result}, processor::{Processor, ProcessorBuilder}, retrievers::{archive::Archive, politeness::Hosts}, sources};
+
+/// Runs every scheduled source whenever it is due until SIGTERM or Ctrl-C. Sources that are due at the same time are
+/// run by one `Processor` on a connection of its own, while the sources of earlier runs may still be running, so a
+/// slow source never holds back the schedule of the others. Runs in progress are finished before shutting down.
+pub async fn run(conn: &mut SqliteConnection, sources_file: &Path, archive: Option<Archive>, warc: Option<PathBuf>, states: &[State], concurrency: usize) -> Result<()> {
+	let config = Config::load(sources_file)?;
+
+	let scheduled: Vec<_> = config.sources.iter()
+		.filter(|source| source.enabled && source.schedule.is_some())
+		.filter(|source| states.is_empty() || states.contains(&source.state))
+		.collect();
+
+	if scheduled.is_empty() {
+		return Err(Error::config("No enabled source has a schedule"));
+	}
+
+	let now = Utc::now();
+	let mut next_runs: HashMap<State, DateTime<Utc>> = HashMap::new();
+	for source in scheduled.iter() {
+		let previous_run = get_last_scrape_run(conn, source.state.into(), &[]).map_err(Error::storage(source.state))?
+			.map(|run| run.started_at.and_utc());
+
+		schedule_next(&mut next_runs, source.state, source.schedule.as_ref().unwrap().next_run(now, previous_run));
+	}
+
+	let mut terminate = signal(SignalKind::terminate()).map_err(|source| Error::Io { context: "could not listen for SIGTERM".to_string(), source })?;
+	let shutdown = async {
+		tokio::select! {
+			_ = terminate.recv() => {},
+			_ = tokio::signal::ctrl_c() => {},
+		}
+	};
+	tokio::pin!(shutdown);
+
+	// Runs that overlap or follow one another request the same hosts, they wait on the same token buckets
+	let hosts = Arc::new(Hosts::new(config.politeness.clone()));
+	let mut running = FuturesUnordered::new();
+	let mut running_states: HashSet<State> = HashSet::new();
+	let mut stopping = false;
+
+	loop {
+		// Sources still running are scheduled again once they finish
+		let next_run = next_runs.iter().filter(|(state, _)| !running_states.contains(state)).map(|(_, next_run)| *next_run).min();
+		if next_run.is_none() && running.is_empty() {
+			println!("No source has a scheduled run left");
+			break;
+		}
+
+		let wait = next_run.map(|next_run| (next_run - Utc::now()).to_std().unwrap_or_default()).unwrap_or_default();
+
+		tokio::select! {
+			_ = tokio::time::sleep(wait), if next_run.is_some() && !stopping => {
+				let now = Utc::now();
+				let due: Vec<State> = next_runs.iter()
+					.filter(|(state, next_run)| **next_run <= now && !running_states.contains(state))
+					.map(|(state, _)| *state)
+					.collect();
+				if due.is_empty() {
+					continue;
+				}
+
+				for state in due.iter() {
+					let late = (now - next_runs[state]).to_std().unwrap_or_default();
+					if late.as_secs() > 0 {
+						println!("Run of {:?} due at {} started {} late", state, next_runs[state], humantime::format_duration(std::time::Duration::from_secs(late.as_secs())));
+					}
+				}
+
+				// Returning here would drop the runs in progress along with their locks
+				let processor = match processor(&config, &due, &hosts, archive.as_ref(), warc.as_ref(), concurrency) {
+					Ok(processor) => processor,
+					Err(err) => {
+						eprintln!("{}", err);
+						for state in due {
+							let schedule = config.source(state).and_then(|source| source.schedule.as_ref()).unwrap();
+							schedule_next(&mut next_runs, state, schedule.next_run(Utc::now(), Some(now)));
+						}
+						continue;
+					},
+				};
+
+				running_states.extend(due.iter().copied());
+				running.push(async move {
+					let result = match establish_connection() {
+						Ok(mut conn) => processor.process(&mut conn).await,
+						Err(err) => Err(err),
+					};
+
+					(due, now, result)
+				});
+			},
+			Some((due, started, result)) = running.next(), if !running.is_empty() => {
+				// Failures are recorded in the run history, the daemon keeps going and retries at the next scheduled run
+				if let Err(err) = result {
+					eprintln!("{}", err);
+				}
+
+				for state in due {
+					running_states.remove(&state);
+					next_runs.remove(&state);
+
+					let schedule = config.source(state).and_then(|source| source.schedule.as_ref()).unwrap();
+					schedule_next(&mut next_runs, state, schedule.next_run(Utc::now(), Some(started)));
+				}
+
+				if stopping && running.is_empty() {
+					break;
+				}
+			},
+			_ = &mut shutdown, if !stopping => {
+				if running.is_empty() {
+					break;
+				}
+
+				println!("Shutting down once the current runs finish");
+				stopping = true;
+			},
+		}
+	}
+
+	println!("Daemon stopped");
+	Ok(())
+}
+
+fn processor(config: &Config, due: &[State], hosts: &Arc<Hosts>, archive: Option<&Archive>, warc: Option<&PathBuf>, concurrency: usize) -> Result<Processor> {
+	let mut builder = ProcessorBuilder::new()
+		.sources(sources::registered_for(config, due)?)
+		.max_concurrency(concurrency)
+		.hosts(hosts.clone())
+		.http(config.http.clone());
+
+	if let Some(archive) = archive {
+		builder = builder.archive(archive.clone());
+	}
+
+	if let Some(warc) = warc {
+		builder = builder.warc(warc.clone());
+	}
+
+	builder.build()
+}
+
+fn schedule_next(next_runs: &mut HashMap<State, DateTime<Utc>>, state: State, next_run: Option<DateTime<Utc>>) {
+	match next_run {
+		Some(next_run) => {
+			println!("Next run of {:?} at {}", state, next_run);
+			next_runs.insert(state, next_run);
+		},
+		None => println!("{:?} has no scheduled run left", state),
+	}
+}
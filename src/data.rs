@@ -1,21 +1,68 @@
 use std::env;
 
-use diesel::{SqliteConnection, Connection, RunQueryDsl, QueryDsl, dsl::sql, ExpressionMethods};
+use chrono::NaiveDateTime;
+use diesel::{SqliteConnection, Connection, ConnectionError, connection::SimpleConnection, RunQueryDsl, QueryDsl, QueryResult, dsl::sql, BoolExpressionMethods, ExpressionMethods, NullableExpressionMethods};
 use dotenvy::dotenv;
 
-use crate::{schema::{breach_data::{self}, classification, last_retrieved}, datamodels::{BreachData, NewBreachData, NewClassification, Classification, LastRetrieved, NewLastRetrieved, State}, dto::Breach};
+use crate::{schema::{backfill_checkpoint, breach_data::{self}, classification, fetched_page, http_cache, last_retrieved, notice_document, scrape_run, source_lock}, datamodels::{NewSourceLock, FetchedPage, HttpCache, NewFetchedPage, NewNoticeDocument, BackfillCheckpoint, NewBackfillCheckpoint, BackfillProgress, BreachData, NewBreachData, NewClassification, Classification, LastRetrieved, NewLastRetrieved, State, ScrapeRun, NewScrapeRun, FinishedScrapeRun, RunStatus}, dto::Breach, error::Error};
 
-pub fn establish_connection() -> SqliteConnection {
+/// Connections wait this long for a write another connection holds instead of failing right away, the daemon writes
+/// from one connection per running batch of sources
+const BUSY_TIMEOUT_MS: u32 = 30_000;
+
+pub fn establish_connection() -> Result<SqliteConnection, Error> {
 	dotenv().ok();
 
-	let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-	SqliteConnection::establish(&database_url)
-			.unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+	let database_url = env::var("DATABASE_URL").map_err(|_| Error::config("DATABASE_URL must be set"))?;
+	let mut conn = SqliteConnection::establish(&database_url)
+		.map_err(|source| Error::Connection { database_url: database_url.clone(), source })?;
+
+	conn.batch_execute(&format!("PRAGMA busy_timeout = {};", BUSY_TIMEOUT_MS))
+		.map_err(|err| Error::Connection { database_url, source: ConnectionError::CouldntSetupConfiguration(err) })?;
+
+	Ok(conn)
 }
 
-pub fn create_breach_data(conn: &mut SqliteConnection, data: &crate::dto::Breach) -> Result<(usize, usize), String> {
+pub fn create_breach_data(conn: &mut SqliteConnection, data: &crate::dto::Breach) -> QueryResult<(usize, usize)> {
 	let mut breach_inserted = 0;
-	// Creates the values to be stored
+	let (bd, mut classes) = new_breach_data(data);
+
+	let new_breach_id;
+
+	// Checks if breach already exists, if so we do not need to use the existing record ID for all classifications
+	let existing_breach = find_breach(conn, &bd)?;
+
+	if let Some(existing_breach) = existing_breach {
+		new_breach_id = existing_breach.id;
+	}
+	else {
+		_ = diesel::insert_into(breach_data::table)
+			.values(bd)
+			.execute(conn)?;
+
+		new_breach_id = breach_data::table.find(sql("last_insert_rowid()")).get_result::<BreachData>(conn)?.id;
+		breach_inserted = 1;
+	}
+	// ---
+
+	// Sets breach_id on each classification
+	classes.iter_mut().for_each(|classification| classification.breach_data_id = new_breach_id);
+	// ---
+
+	// Checks if classifications already exist, if not, insert
+	if breach_inserted == 0 {
+		classes = missing_classifications(conn, classes)?;
+	}
+	let inserted = diesel::insert_into(classification::table)
+		.values(classes)
+		.execute(conn)?;
+	// ---
+
+	Ok((breach_inserted, inserted))
+}
+
+/// Creates the values to be stored for a breach, classifications are not yet linked to a breach_data record
+pub fn new_breach_data(data: &crate::dto::Breach) -> (NewBreachData, Vec<NewClassification>) {
 	let bd = NewBreachData {
 		date_reported: data.date_reported,
 		organization_name: data.organization_name.clone(),
@@ -27,7 +74,7 @@ pub fn create_breach_data(conn: &mut SqliteConnection, data: &crate::dto::Breach
 		link: data.link.clone(),
 	};
 
-	let mut classes: Vec<NewClassification> = data.leaked_info.iter().map(|r| crate::datamodels::NewClassification {
+	let classes = data.leaked_info.iter().map(|r| crate::datamodels::NewClassification {
 		breach_data_id: 0,
 		content: match r {
 			crate::dto::ClassificationType::Unknown(c, _) => c.clone(),
@@ -35,59 +82,44 @@ pub fn create_breach_data(conn: &mut SqliteConnection, data: &crate::dto::Breach
 		},
 		classification_type: r.into()
 	}).collect();
-	// ---
 
-	let new_breach_id;
+	(bd, classes)
+}
 
-	// Checks if breach already exists, if so we do not need to use the existing record ID for all classifications
+/// Stored breach with the same reported date, organization and location, which is what makes two breaches the same
+pub fn find_breach(conn: &mut SqliteConnection, bd: &NewBreachData) -> QueryResult<Option<BreachData>> {
 	let existing_breach = breach_data::dsl::breach_data
 		.filter(breach_data::dsl::date_reported.eq(&bd.date_reported))
 		.filter(breach_data::dsl::organization_name.eq(&bd.organization_name))
 		.filter(breach_data::dsl::loc.eq(&bd.loc))
-		.load::<BreachData>(conn).expect("Could not query breaches");
-
-	if existing_breach.len() == 0 {
-		_ = diesel::insert_into(breach_data::table)
-			.values(bd)
-			.execute(conn)
-			.expect("Error inserting");
-
-		new_breach_id = breach_data::table.find(sql("last_insert_rowid()")).get_result::<BreachData>(conn).expect("Error retrieving inserted breach data").id;
-		breach_inserted = 1;
-	}
-	else {
-		new_breach_id = existing_breach[0].id;
-	}
-	// ---
+		.load::<BreachData>(conn)?;
 
-	// Sets breach_id on each classification
-	classes.iter_mut().for_each(|classification| classification.breach_data_id = new_breach_id);
-	// ---
+	Ok(existing_breach.into_iter().next())
+}
 
-	// Checks if classifications already exist, if not, insert
-	if breach_inserted == 0 {
-		classes = classes.iter().filter(|class| classification::dsl::classification
+/// Classifications whose type is not yet stored for their breach_data record
+pub fn missing_classifications(conn: &mut SqliteConnection, classes: Vec<NewClassification>) -> QueryResult<Vec<NewClassification>> {
+	let mut missing = vec!();
+	for class in classes {
+		let existing = classification::dsl::classification
 			.filter(classification::dsl::breach_data_id.eq(class.breach_data_id))
 			.filter(classification::dsl::classification_type.eq(class.classification_type))
-			.load::<Classification>(conn).unwrap().len() == 0).map(|class| class.clone()).collect::<Vec<NewClassification>>();
+			.load::<Classification>(conn)?;
+
+		if existing.is_empty() {
+			missing.push(class);
+		}
 	}
-	let inserted = diesel::insert_into(classification::table)
-		.values(classes)
-		.execute(conn)
-		.expect("Error inserting classifications");
-	// ---
 
-	Ok((breach_inserted, inserted))
+	Ok(missing)
 }
 
-pub fn get_breaches(conn: &mut SqliteConnection) -> Result<Vec<Breach>, String> {
+pub fn get_breaches(conn: &mut SqliteConnection) -> QueryResult<Vec<Breach>> {
 	let bdresults = breach_data::dsl::breach_data
-		.load::<BreachData>(conn)
-		.expect("Error loading breach data");
+		.load::<BreachData>(conn)?;
 
 	let cresults = classification::dsl::classification
-		.load::<Classification>(conn)
-		.expect("Error retrieving classifications");
+		.load::<Classification>(conn)?;
 
 	let mut breaches: Vec<Breach> = vec!();
 	for breach in bdresults.iter() {
@@ -98,22 +130,159 @@ pub fn get_breaches(conn: &mut SqliteConnection) -> Result<Vec<Breach>, String>
 	Ok(breaches)
 }
 
-pub fn insert_last_retrieved(conn: &mut SqliteConnection, last_retrieved: NewLastRetrieved) -> Result<(), String> {
-	let _ = diesel::insert_into(last_retrieved::table).values([last_retrieved]).execute(conn).expect("Failed inserting last retrieved");
+pub fn insert_last_retrieved(conn: &mut SqliteConnection, last_retrieved: NewLastRetrieved) -> QueryResult<()> {
+	let _ = diesel::insert_into(last_retrieved::table).values([last_retrieved]).execute(conn)?;
 
 	Ok(())
 }
 
-pub fn get_last_retrieved(conn: &mut SqliteConnection, location: State) -> Result<Option<LastRetrieved>, String> {
+pub fn get_last_retrieved(conn: &mut SqliteConnection, location: State) -> QueryResult<Option<LastRetrieved>> {
 	let results = last_retrieved::dsl::last_retrieved
 		.filter(last_retrieved::dsl::loc.eq(location))
 		.order(last_retrieved::dsl::retrieved_date.desc())
 		.limit(1)
-		.load::<LastRetrieved>(conn)
-		.expect(&format!("Could not retrieve last retrieved date for location {:?}", location));
+		.load::<LastRetrieved>(conn)?;
 
 	if results.len() == 1 {
 		return Ok(Some(results[0]))
 	}
 	Ok(None)
-}
\ No newline at end of file
+}
+
+pub fn create_scrape_run(conn: &mut SqliteConnection, run: NewScrapeRun) -> QueryResult<i32> {
+	_ = diesel::insert_into(scrape_run::table).values(run).execute(conn)?;
+
+	Ok(scrape_run::table.find(sql("last_insert_rowid()")).get_result::<ScrapeRun>(conn)?.id)
+}
+
+pub fn finish_scrape_run(conn: &mut SqliteConnection, id: i32, run: FinishedScrapeRun) -> QueryResult<()> {
+	_ = diesel::update(scrape_run::table.find(id)).set(run).execute(conn)?;
+
+	Ok(())
+}
+
+/// Last run of a location with any of the given statuses, or with any status when none are given
+pub fn get_last_scrape_run(conn: &mut SqliteConnection, location: State, statuses: &[RunStatus]) -> QueryResult<Option<ScrapeRun>> {
+	let mut query = scrape_run::dsl::scrape_run
+		.filter(scrape_run::dsl::loc.eq(location))
+		.order(scrape_run::dsl::started_at.desc())
+		.limit(1)
+		.into_boxed();
+
+	if !statuses.is_empty() {
+		query = query.filter(scrape_run::dsl::run_status.eq_any(statuses.to_vec()));
+	}
+
+	Ok(query.load::<ScrapeRun>(conn)?.into_iter().next())
+}
+
+/// Takes the lock of a location unless another holder has it and it has not expired yet, returns whether it was taken
+pub fn try_lock_source(conn: &mut SqliteConnection, lock: NewSourceLock) -> QueryResult<bool> {
+	conn.transaction(|conn| {
+		_ = diesel::delete(source_lock::table
+			.filter(source_lock::dsl::loc.eq(lock.loc))
+			.filter(source_lock::dsl::expires_at.lt(lock.acquired_at)))
+			.execute(conn)?;
+
+		let inserted = diesel::insert_or_ignore_into(source_lock::table).values(lock).execute(conn)?;
+
+		Ok(inserted == 1)
+	})
+}
+
+/// Moves the expiry of every lock of a holder, returns how many locks it holds
+pub fn renew_source_locks(conn: &mut SqliteConnection, holder: &str, expires_at: NaiveDateTime) -> QueryResult<usize> {
+	diesel::update(source_lock::table.filter(source_lock::dsl::holder.eq(holder)))
+		.set(source_lock::dsl::expires_at.eq(expires_at))
+		.execute(conn)
+}
+
+pub fn unlock_source(conn: &mut SqliteConnection, location: State, holder: &str) -> QueryResult<()> {
+	_ = diesel::delete(source_lock::table
+		.filter(source_lock::dsl::loc.eq(location))
+		.filter(source_lock::dsl::holder.eq(holder)))
+		.execute(conn)?;
+
+	Ok(())
+}
+
+/// Checkpoints of a location's backfill, one per url
+pub fn get_backfill_checkpoints(conn: &mut SqliteConnection, location: State) -> QueryResult<Vec<BackfillCheckpoint>> {
+	backfill_checkpoint::dsl::backfill_checkpoint
+		.filter(backfill_checkpoint::dsl::loc.eq(location))
+		.load::<BackfillCheckpoint>(conn)
+}
+
+pub fn create_backfill_checkpoint(conn: &mut SqliteConnection, checkpoint: NewBackfillCheckpoint) -> QueryResult<BackfillCheckpoint> {
+	_ = diesel::insert_into(backfill_checkpoint::table).values(checkpoint).execute(conn)?;
+
+	backfill_checkpoint::table.find(sql("last_insert_rowid()")).get_result::<BackfillCheckpoint>(conn)
+}
+
+pub fn update_backfill_checkpoint(conn: &mut SqliteConnection, id: i32, progress: BackfillProgress) -> QueryResult<()> {
+	_ = diesel::update(backfill_checkpoint::table.find(id)).set(progress).execute(conn)?;
+
+	Ok(())
+}
+
+pub fn delete_backfill_checkpoints(conn: &mut SqliteConnection, location: State) -> QueryResult<usize> {
+	diesel::delete(backfill_checkpoint::table.filter(backfill_checkpoint::dsl::loc.eq(location))).execute(conn)
+}
+
+pub fn create_fetched_page(conn: &mut SqliteConnection, page: NewFetchedPage) -> QueryResult<()> {
+	_ = diesel::insert_into(fetched_page::table).values(page).execute(conn)?;
+
+	Ok(())
+}
+
+/// Pages fetched with a successful status, of one run or of every run, oldest first
+pub fn get_fetched_pages(conn: &mut SqliteConnection, run_id: Option<i32>) -> QueryResult<Vec<FetchedPage>> {
+	let mut query = fetched_page::dsl::fetched_page
+		.filter(fetched_page::dsl::status.between(200, 299))
+		.order((fetched_page::dsl::fetched_at.asc(), fetched_page::dsl::id.asc()))
+		.into_boxed();
+
+	if let Some(run_id) = run_id {
+		query = query.filter(fetched_page::dsl::run_id.eq(run_id));
+	}
+
+	query.load::<FetchedPage>(conn)
+}
+
+pub fn get_http_cache(conn: &mut SqliteConnection, location: State) -> QueryResult<Vec<HttpCache>> {
+	http_cache::dsl::http_cache
+		.filter(http_cache::dsl::loc.eq(location))
+		.load::<HttpCache>(conn)
+}
+
+pub fn save_http_cache(conn: &mut SqliteConnection, cache: HttpCache) -> QueryResult<()> {
+	_ = diesel::replace_into(http_cache::table).values(cache).execute(conn)?;
+
+	Ok(())
+}
+
+/// Ids, links and failed attempts at the link of a location's breaches whose notice letter is due for a download
+pub fn get_missing_notice_documents(conn: &mut SqliteConnection, location: State, now: NaiveDateTime) -> QueryResult<Vec<(i32, String, i32)>> {
+	let documents = breach_data::table
+		.left_join(notice_document::table)
+		.filter(breach_data::dsl::loc.eq(location))
+		.filter(breach_data::dsl::link.is_not_null())
+		.filter(notice_document::dsl::id.nullable().is_null()
+			.or(notice_document::dsl::link.nullable().ne(breach_data::dsl::link))
+			.or(notice_document::dsl::retry_at.le(now)))
+		.select((breach_data::dsl::id, breach_data::dsl::link.assume_not_null(), notice_document::dsl::link.nullable(), notice_document::dsl::attempts.nullable()))
+		.order(breach_data::dsl::id.asc())
+		.load::<(i32, String, Option<String>, Option<i32>)>(conn)?;
+
+	Ok(documents.into_iter().map(|(id, link, attempted_link, attempts)| {
+		let attempts = if attempted_link.as_ref() == Some(&link) { attempts.unwrap_or_default() } else { 0 };
+		(id, link, attempts)
+	}).collect())
+}
+
+/// Records the last attempt at the notice letter of a breach, replacing the one before
+pub fn save_notice_document(conn: &mut SqliteConnection, document: NewNoticeDocument) -> QueryResult<()> {
+	_ = diesel::replace_into(notice_document::table).values(document).execute(conn)?;
+
+	Ok(())
+}
@@ -1,5 +1,5 @@
 use chrono::{NaiveDateTime};
-use diesel::{prelude::*, AsExpression, sql_types::*, FromSqlRow, serialize::{self, Output, ToSql}, deserialize::{self, FromSql}, backend::{self, Backend}};
+use diesel::{prelude::*, AsExpression, sql_types::*, FromSqlRow, serialize::{self, Output, ToSql}, deserialize::{self, FromSql}, backend::Backend};
 
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, FromSqlRow, AsExpression, PartialEq)]
@@ -41,7 +41,7 @@ where
 	DB: Backend,
 	i32: FromSql<Integer, DB>,
 {
-	fn from_sql(bytes: backend::RawValue<DB>) -> deserialize::Result<Self> {
+	fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
 		match i32::from_sql(bytes)? {
 			0 => Ok(BreachType::Unknown),
 			1 => Ok(BreachType::HackerUnauthorizedAccess),
@@ -90,7 +90,7 @@ where
 	DB: Backend,
 	i32: FromSql<Integer, DB>,
 {
-	fn from_sql(bytes: backend::RawValue<DB>) -> deserialize::Result<Self> {
+	fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
 		match i32::from_sql(bytes)? {
 			1 => Ok(State::WA),
 			2 => Ok(State::OR),
@@ -168,7 +168,7 @@ where
 	DB: Backend,
 	i32: FromSql<Integer, DB>,
 {
-	fn from_sql(bytes: backend::RawValue<DB>) -> deserialize::Result<Self> {
+	fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
 		match i32::from_sql(bytes)? {
 			0 => Ok(ClassificationType::Unknown),
 			1 => Ok(ClassificationType::Name),
@@ -255,3 +255,215 @@ pub struct NewLastRetrieved {
 	pub loc: State,
 	pub retrieved_date: NaiveDateTime,
 }
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, FromSqlRow, AsExpression, PartialEq)]
+#[diesel(sql_type = Integer)]
+pub enum RunStatus {
+	Running = 0,
+	Succeeded = 1,
+	Failed = 2,
+	/// The source responded that nothing changed since the last run
+	Unchanged = 3,
+}
+
+impl<DB> ToSql<Integer, DB> for RunStatus
+where
+	DB: Backend,
+	i32: ToSql<Integer, DB>,
+{
+	fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DB>) -> serialize::Result {
+		match self {
+			RunStatus::Running => 0.to_sql(out),
+			RunStatus::Succeeded => 1.to_sql(out),
+			RunStatus::Failed => 2.to_sql(out),
+			RunStatus::Unchanged => 3.to_sql(out),
+		}
+	}
+}
+
+impl<DB> FromSql<Integer, DB> for RunStatus
+where
+	DB: Backend,
+	i32: FromSql<Integer, DB>,
+{
+	fn from_sql(bytes: DB::RawValue<'_>) -> deserialize::Result<Self> {
+		match i32::from_sql(bytes)? {
+			0 => Ok(RunStatus::Running),
+			1 => Ok(RunStatus::Succeeded),
+			2 => Ok(RunStatus::Failed),
+			3 => Ok(RunStatus::Unchanged),
+			x => Err(format!("Unrecognized variant {}", x).into()),
+		}
+	}
+}
+
+#[derive(Queryable, Debug, PartialEq, Identifiable, Clone)]
+#[diesel(table_name = crate::schema::scrape_run)]
+pub struct ScrapeRun {
+	pub id: i32,
+	pub loc: State,
+	pub run_status: RunStatus,
+	pub started_at: NaiveDateTime,
+	pub finished_at: Option<NaiveDateTime>,
+	pub pages_fetched: i32,
+	pub rows_parsed: i32,
+	pub rows_inserted: i32,
+	pub classifications_added: i32,
+	pub error_count: i32,
+	pub errors: Option<String>,
+	pub watermark: Option<NaiveDateTime>,
+	/// What kind of failure a failed run ran into, see `ErrorKind::name`
+	pub error_kind: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::scrape_run)]
+pub struct NewScrapeRun {
+	pub loc: State,
+	pub run_status: RunStatus,
+	pub started_at: NaiveDateTime,
+}
+
+#[derive(Debug, AsChangeset)]
+#[diesel(table_name = crate::schema::scrape_run)]
+pub struct FinishedScrapeRun {
+	pub run_status: RunStatus,
+	pub started_at: NaiveDateTime,
+	pub finished_at: NaiveDateTime,
+	pub pages_fetched: i32,
+	pub rows_parsed: i32,
+	pub rows_inserted: i32,
+	pub classifications_added: i32,
+	pub error_count: i32,
+	pub errors: Option<String>,
+	pub watermark: Option<NaiveDateTime>,
+	/// What kind of failure a failed run ran into, see `ErrorKind::name`
+	pub error_kind: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::source_lock)]
+pub struct NewSourceLock {
+	pub loc: State,
+	pub holder: String,
+	pub acquired_at: NaiveDateTime,
+	pub expires_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Debug, PartialEq, Identifiable, Clone)]
+#[diesel(table_name = crate::schema::backfill_checkpoint)]
+pub struct BackfillCheckpoint {
+	pub id: i32,
+	pub loc: State,
+	pub base_url: String,
+	pub page: i32,
+	pub next_url_part: Option<String>,
+	pub pages_fetched: i32,
+	pub rows_inserted: i32,
+	pub completed: bool,
+	pub updated_at: NaiveDateTime,
+	pub group_name: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::backfill_checkpoint)]
+pub struct NewBackfillCheckpoint {
+	pub loc: State,
+	pub base_url: String,
+	pub updated_at: NaiveDateTime,
+}
+
+/// Progress after a completed page, `page`, `next_url_part` and `group_name` are where the backfill continues from
+#[derive(Debug, AsChangeset)]
+#[diesel(table_name = crate::schema::backfill_checkpoint, treat_none_as_null = true)]
+pub struct BackfillProgress {
+	pub page: i32,
+	pub next_url_part: Option<String>,
+	pub group_name: Option<String>,
+	pub pages_fetched: i32,
+	pub rows_inserted: i32,
+	pub completed: bool,
+	pub updated_at: NaiveDateTime,
+}
+
+impl From<&BackfillCheckpoint> for BackfillProgress {
+	fn from(value: &BackfillCheckpoint) -> Self {
+		BackfillProgress {
+			page: value.page,
+			next_url_part: value.next_url_part.clone(),
+			group_name: value.group_name.clone(),
+			pages_fetched: value.pages_fetched,
+			rows_inserted: value.rows_inserted,
+			completed: value.completed,
+			updated_at: chrono::Utc::now().naive_utc(),
+		}
+	}
+}
+
+#[derive(Queryable, Debug, PartialEq, Identifiable, Clone)]
+#[diesel(table_name = crate::schema::fetched_page)]
+pub struct FetchedPage {
+	pub id: i32,
+	pub run_id: Option<i32>,
+	pub loc: State,
+	pub url: String,
+	pub method: String,
+	/// JSON object of header names to values
+	pub request_headers: String,
+	pub status: i32,
+	/// JSON object of header names to values
+	pub response_headers: String,
+	pub fetched_at: NaiveDateTime,
+	pub content_hash: String,
+	pub size: i32,
+	/// sha256 of the request body, none for pages archived before it was recorded
+	pub request_body_hash: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::fetched_page)]
+pub struct NewFetchedPage {
+	pub run_id: Option<i32>,
+	pub loc: State,
+	pub url: String,
+	pub method: String,
+	pub request_headers: String,
+	pub status: i32,
+	pub response_headers: String,
+	pub fetched_at: NaiveDateTime,
+	pub content_hash: String,
+	pub size: i32,
+	pub request_body_hash: Option<String>,
+}
+
+/// ETag and Last-Modified of the last stored response of a url, sent back so an unchanged page is not downloaded again
+#[derive(Queryable, Insertable, Debug, PartialEq, Clone)]
+#[diesel(table_name = crate::schema::http_cache)]
+pub struct HttpCache {
+	pub loc: State,
+	pub url: String,
+	pub etag: Option<String>,
+	pub last_modified: Option<String>,
+	pub updated_at: NaiveDateTime,
+}
+
+/// The last attempt at downloading the notice letter a breach links to, stored in the archive under `content_hash`
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::notice_document)]
+pub struct NewNoticeDocument {
+	pub breach_data_id: i32,
+	pub link: String,
+	pub content_hash: Option<String>,
+	pub mime_type: Option<String>,
+	pub size: Option<i32>,
+	pub fetched_at: Option<NaiveDateTime>,
+	/// Status of the response, none when there was none
+	pub status: Option<i32>,
+	pub error: Option<String>,
+	/// Failed attempts in a row at the link
+	pub attempts: i32,
+	pub attempted_at: NaiveDateTime,
+	/// When a failed download is attempted again
+	pub retry_at: Option<NaiveDateTime>,
+}
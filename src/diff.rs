@@ -0,0 +1,145 @@
+use std::{collections::HashSet, fmt};
+
+use chrono::NaiveDateTime;
+use diesel::{QueryResult, SqliteConnection};
+use serde::Serialize;
+
+use crate::{data::{find_breach, missing_classifications, new_breach_data}, dto::{Breach, State}};
+
+/// What a dry run would have written, per source
+#[derive(Debug, Default, Serialize)]
+pub struct DryRunReport {
+	pub states: Vec<StateDiff>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StateDiff {
+	pub state: State,
+	pub parsed: usize,
+	/// Breaches that are not stored yet
+	pub new: Vec<Breach>,
+	/// Stored breaches whose parsed fields differ, these are not updated by a scrape
+	pub changed: Vec<ChangedBreach>,
+	/// Classifications that would be added to new and stored breaches
+	pub classifications: Vec<AddedClassification>,
+	pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangedBreach {
+	pub id: i32,
+	pub date_reported: NaiveDateTime,
+	pub organization_name: String,
+	pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+	pub field: &'static str,
+	pub stored: String,
+	pub parsed: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddedClassification {
+	/// Stored breach the classification is added to, none when the breach itself is new
+	pub breach_id: Option<i32>,
+	pub date_reported: NaiveDateTime,
+	pub organization_name: String,
+	pub classification_type: String,
+	pub content: String,
+}
+
+impl StateDiff {
+	pub fn failed(state: State, error: String) -> StateDiff {
+		StateDiff { state, parsed: 0, new: vec!(), changed: vec!(), classifications: vec!(), error: Some(error) }
+	}
+}
+
+/// Compares parsed breaches with the stored ones, matching them the same way `create_breach_data` does
+pub fn diff_breaches(conn: &mut SqliteConnection, state: State, breaches: Vec<Breach>) -> QueryResult<StateDiff> {
+	let mut diff = StateDiff { state, parsed: breaches.len(), new: vec!(), changed: vec!(), classifications: vec!(), error: None };
+	let mut new_keys = HashSet::new();
+
+	for breach in breaches {
+		let (bd, mut classes) = new_breach_data(&breach);
+
+		let breach_id = match find_breach(conn, &bd)? {
+			Some(existing) => {
+				let mut changes = vec!();
+				compare(&mut changes, "date_of_breach", &existing.date_of_breach, &bd.date_of_breach);
+				compare(&mut changes, "affected_count", &existing.affected_count, &bd.affected_count);
+				compare(&mut changes, "affected_count_local", &existing.affected_count_local, &bd.affected_count_local);
+				compare(&mut changes, "link", &existing.link, &bd.link);
+				compare(&mut changes, "breach_type", &existing.breach_type, &bd.breach_type);
+
+				if !changes.is_empty() {
+					diff.changed.push(ChangedBreach { id: existing.id, date_reported: bd.date_reported, organization_name: bd.organization_name.clone(), changes });
+				}
+
+				classes.iter_mut().for_each(|classification| classification.breach_data_id = existing.id);
+				classes = missing_classifications(conn, classes)?;
+
+				Some(existing.id)
+			},
+			None => {
+				// The same breach listed twice is only inserted once
+				if !new_keys.insert((bd.date_reported, bd.organization_name.clone())) {
+					continue;
+				}
+
+				None
+			},
+		};
+
+		diff.classifications.extend(classes.into_iter().map(|class| AddedClassification {
+			breach_id,
+			date_reported: bd.date_reported,
+			organization_name: bd.organization_name.clone(),
+			classification_type: format!("{:?}", class.classification_type),
+			content: class.content,
+		}));
+
+		if breach_id.is_none() {
+			diff.new.push(breach);
+		}
+	}
+
+	Ok(diff)
+}
+
+fn compare<T: PartialEq + fmt::Debug>(changes: &mut Vec<FieldChange>, field: &'static str, stored: &T, parsed: &T) {
+	if stored != parsed {
+		changes.push(FieldChange { field, stored: format!("{:?}", stored), parsed: format!("{:?}", parsed) });
+	}
+}
+
+impl fmt::Display for DryRunReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for diff in self.states.iter() {
+			if let Some(error) = &diff.error {
+				writeln!(f, "{:?}: failed, {}", diff.state, error)?;
+				continue;
+			}
+
+			writeln!(f, "{:?}: {} parsed, {} new, {} changed, {} classification(s) to add", diff.state, diff.parsed, diff.new.len(), diff.changed.len(), diff.classifications.len())?;
+
+			for breach in diff.new.iter() {
+				writeln!(f, "\t+ {} {}", breach.date_reported.date(), breach.organization_name)?;
+			}
+
+			for changed in diff.changed.iter() {
+				writeln!(f, "\t~ {} {} (id {})", changed.date_reported.date(), changed.organization_name, changed.id)?;
+				for change in changed.changes.iter() {
+					writeln!(f, "\t\t{}: {} -> {}", change.field, change.stored, change.parsed)?;
+				}
+			}
+
+			for class in diff.classifications.iter().filter(|class| class.breach_id.is_some()) {
+				writeln!(f, "\t+ {} {}: {} {}", class.date_reported.date(), class.organization_name, class.classification_type, class.content)?;
+			}
+		}
+
+		Ok(())
+	}
+}
@@ -1,4 +1,7 @@
+use std::str::FromStr;
+
 use chrono::NaiveDateTime;
+use clap::ValueEnum;
 use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
@@ -46,7 +49,8 @@ impl From<BreachType> for crate::datamodels::BreachType {
 	}
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[value(rename_all = "UPPER")]
 pub enum State {
 	WA = 1,
 	OR = 2,
@@ -55,6 +59,20 @@ pub enum State {
 	HI = 5,
 }
 
+impl State {
+	pub fn all() -> Vec<State> {
+		State::value_variants().to_vec()
+	}
+}
+
+impl FromStr for State {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		<State as ValueEnum>::from_str(s.trim(), true).map_err(|_| format!("Unrecognized state {}", s.trim().to_uppercase()))
+	}
+}
+
 impl From<crate::datamodels::State> for State {
 	fn from(value: crate::datamodels::State) -> Self {
 		match value {
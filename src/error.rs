@@ -0,0 +1,159 @@
+use std::{io, path::PathBuf};
+
+use crate::{dto::State, parsers::ParseError};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("{state:?}: could not fetch page {page} from {url}: {source}")]
+	Network {
+		state: State,
+		url: String,
+		page: i32,
+		#[source]
+		source: reqwest::Error,
+	},
+	#[error("{state:?}: {url} responded {status} for page {page}")]
+	Status {
+		state: State,
+		url: String,
+		page: i32,
+		status: reqwest::StatusCode,
+	},
+	#[error("{state:?}: {url} timed out for page {page}")]
+	Timeout {
+		state: State,
+		url: String,
+		page: i32,
+	},
+	#[error("{state:?}: {url} responded with more than {limit} bytes for page {page}")]
+	TooLarge {
+		state: State,
+		url: String,
+		page: i32,
+		limit: u64,
+	},
+	#[error("{state:?}: {url} is disallowed by robots.txt")]
+	Disallowed {
+		state: State,
+		url: String,
+	},
+	#[error("{state:?}: no snapshot of {url}")]
+	NotArchived {
+		state: State,
+		url: String,
+	},
+	#[error("{state:?}: stopped at {url} after {max_pages} pages without finding the last page")]
+	TooManyPages {
+		state: State,
+		url: String,
+		max_pages: usize,
+	},
+	#[error("{state:?}: could not parse page {page} from {url}: {source}")]
+	Parse {
+		state: State,
+		url: String,
+		page: i32,
+		#[source]
+		source: ParseError,
+	},
+	#[error("{}: {source}", state.map(|s| format!("{:?}", s)).unwrap_or_else(|| "database".to_string()))]
+	Storage {
+		state: Option<State>,
+		#[source]
+		source: diesel::result::Error,
+	},
+	#[error("could not connect to {database_url}: {source}")]
+	Connection {
+		database_url: String,
+		#[source]
+		source: diesel::ConnectionError,
+	},
+	#[error("{}{message}", path.as_ref().map(|p| format!("{}: ", p.display())).unwrap_or_default())]
+	Config {
+		path: Option<PathBuf>,
+		message: String,
+	},
+	#[error("{context}: {source}")]
+	Io {
+		context: String,
+		#[source]
+		source: io::Error,
+	},
+	#[error("{} source(s) failed", .0.len())]
+	Sources(Vec<Error>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+	/// The site could not be reached or did not respond
+	Network,
+	/// The site responded with an error status that retrying does not fix, such as a page that moved or was removed
+	Rejected,
+	/// The site responded with something the parser does not understand, most likely its layout changed
+	LayoutChanged,
+	/// Another connection holds a lock on the database
+	DatabaseLocked,
+	Storage,
+	Config,
+	Io,
+	/// Errors of different kinds together
+	Multiple,
+}
+
+impl ErrorKind {
+	pub fn name(&self) -> &'static str {
+		match self {
+			ErrorKind::Network => "network",
+			ErrorKind::Rejected => "rejected",
+			ErrorKind::LayoutChanged => "layout changed",
+			ErrorKind::DatabaseLocked => "database locked",
+			ErrorKind::Storage => "storage",
+			ErrorKind::Config => "config",
+			ErrorKind::Io => "io",
+			ErrorKind::Multiple => "multiple",
+		}
+	}
+}
+
+impl Error {
+	/// What kind of failure this is, a group of errors is of the kind all of them share
+	pub fn kind(&self) -> ErrorKind {
+		match self {
+			Error::Network { .. } | Error::Timeout { .. } => ErrorKind::Network,
+			Error::Status { status, .. } if status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS => ErrorKind::Network,
+			Error::Status { .. } | Error::Disallowed { .. } | Error::TooLarge { .. } => ErrorKind::Rejected,
+			Error::Parse { .. } | Error::TooManyPages { .. } => ErrorKind::LayoutChanged,
+			Error::Storage { source: diesel::result::Error::DatabaseError(_, info), .. } if is_lock_message(info.message()) => ErrorKind::DatabaseLocked,
+			Error::Storage { .. } | Error::Connection { .. } => ErrorKind::Storage,
+			Error::Config { .. } => ErrorKind::Config,
+			Error::Io { .. } | Error::NotArchived { .. } => ErrorKind::Io,
+			Error::Sources(errors) => {
+				let mut kinds = errors.iter().map(Error::kind);
+				let first = kinds.next().unwrap_or(ErrorKind::Multiple);
+
+				if kinds.all(|kind| kind == first) { first } else { ErrorKind::Multiple }
+			},
+		}
+	}
+
+	pub fn storage(state: State) -> impl FnOnce(diesel::result::Error) -> Error {
+		move |source| Error::Storage { state: Some(state), source }
+	}
+
+	pub fn config(message: impl Into<String>) -> Error {
+		Error::Config { path: None, message: message.into() }
+	}
+}
+
+impl From<diesel::result::Error> for Error {
+	fn from(source: diesel::result::Error) -> Self {
+		Error::Storage { state: None, source }
+	}
+}
+
+// SQLITE_BUSY and SQLITE_LOCKED are reported by diesel as unknown database errors, only the message tells them apart
+fn is_lock_message(message: &str) -> bool {
+	message.contains("database is locked") || message.contains("database table is locked")
+}
@@ -1,310 +1,235 @@
-use chrono::{NaiveDate, Days};
+use std::{fs::File, io::{self, Write}, path::{Path, PathBuf}, process::ExitCode};
 
+use clap::Parser as _;
+
+pub mod cli;
+pub mod config;
+pub mod daemon;
+pub mod error;
 pub mod retrievers;
 pub mod datamodels;
 pub mod dto;
 pub mod schema;
 pub mod data;
+pub mod diff;
 pub mod parsers;
-
-use data::{establish_connection, create_breach_data, get_last_retrieved};
+pub mod sources;
+pub mod processor;
+pub mod schedule;
+
+use cli::{Cli, Command, BreachFilter, ExportFormat, ReportFormat};
+use config::Config;
+use chrono::Utc;
+use data::{establish_connection, get_fetched_pages, get_last_retrieved, get_last_scrape_run, get_breaches};
+use datamodels::RunStatus;
 use diesel::SqliteConnection;
-use parsers::{Parser, md_parser::MdParser};
-use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, USER_AGENT, CONTENT_LENGTH};
-use retrievers::{multi_page::MultiPage, single_page::SinglePage, Retriever, RetrieverOptions, WebRequestType};
-use serde_json::json;
-
-use crate::{datamodels::NewLastRetrieved, data::insert_last_retrieved, parsers::{ca_parser::CaParser, or_parser::OrParser, wa_parser::WaParser, hi_parser::HiParser}};
+use dto::Breach;
+use error::{Error, Result};
+use processor::{Processor, ProcessorBuilder};
+use retrievers::{archive::Archive, replay::Snapshots};
 
 #[tokio::main]
-async fn main() -> Result<(), ()> {
-	// CA does not support OpenSSL3 so we need to use a custom config that allows legacy redirects
-	std::env::set_var("OPENSSL_CONF", "./openssl_temp.cnf");
-
-	let conn = &mut establish_connection();
-
-	let processor = ProcessorBuilder::new()
-		// .process_option(get_wa_options(conn))
-		// .process_option(get_hi_options(conn))
-		// .process_option(get_ca_options(conn))
-		// .process_option(get_or_options(conn))
-		.process_options(get_md_options(conn))
-		.build().unwrap();
-
-	if let Err(err) = processor.process(conn).await {
-		println!("{:?}", err);
+async fn main() -> ExitCode {
+	let cli = Cli::parse();
+
+	if let Err(err) = run(cli).await {
+		eprintln!("{}", err);
+		return ExitCode::FAILURE;
 	}
 
-	Ok(())
+	ExitCode::SUCCESS
 }
 
-fn create_headers(state: dto::State) -> HeaderMap<HeaderValue> {
-	let mut headers = HeaderMap::new();
-
-	headers.insert(ACCEPT, "*/*".parse().unwrap());
-	headers.insert(USER_AGENT, "breach_tracker".parse().unwrap());
-
-	match state {
-		dto::State::WA => {},
-		dto::State::CA => {},
-		dto::State::OR => {},
-		dto::State::HI => {},
-		dto::State::MD => { headers.insert(CONTENT_LENGTH, "0".parse().unwrap()); },
-	};
+async fn run(cli: Cli) -> Result<()> {
+	let conn = &mut establish_connection()?;
+	let archive = (!cli.no_archive).then(|| Archive::new(cli.archive_dir.clone()));
 
-	headers
-}
+	match cli.command {
+		Command::Scrape { state, concurrency, dry_run: false, documents, .. } => {
+			let processor = processor_builder(&cli.sources, archive, cli.warc_output, &state, concurrency)?.build()?;
+			let scraped = processor.process(conn).await;
 
-fn get_url_generator(state: &dto::State) -> Box<dyn Fn(String, String) -> String + Send> {
-	match state {
-		dto::State::WA => Box::new(|base_url, page| format!("{}{}", base_url, page)),
-		dto::State::CA => Box::new(|base_url, _| base_url.to_string()),
-		dto::State::OR => Box::new(|base_url, _| base_url.to_string()),
-		dto::State::HI => Box::new(|base_url, _| base_url.to_string()),
-		dto::State::MD => Box::new(|base_url, page| {
-			if page == "0" {
-				return base_url;
+			// Letters of the breaches a failed source did store are downloaded all the same
+			if documents {
+				let downloaded = processor.documents(conn).await;
+				scraped.and(downloaded)
 			}
-
-			format!("{}{}", base_url, page)
-		}),
+			else {
+				scraped
+			}
+		},
+		Command::Scrape { state, concurrency, dry_run: true, report, .. } => dry_run(conn, processor_builder(&cli.sources, None, None, &state, concurrency)?.build()?, report).await,
+		Command::Backfill { state, concurrency, restart } => processor_builder(&cli.sources, archive, cli.warc_output, &state, concurrency)?.build()?.backfill(conn, restart).await,
+		Command::Replay { state, concurrency, dir, warc, run, dry_run: replay_dry_run, report } => {
+			let snapshots = match (dir, warc) {
+				(Some(dir), _) => Snapshots::Directory(dir),
+				(None, Some(warc)) => Snapshots::warc(&warc).map_err(|source| Error::Io { context: format!("could not read {}", warc.display()), source })?,
+				(None, None) => Snapshots::archived(Archive::new(cli.archive_dir.clone()), get_fetched_pages(conn, run)?),
+			};
+			let processor = processor_builder(&cli.sources, None, None, &state, concurrency)?.replay(snapshots).build()?;
+
+			if replay_dry_run {
+				dry_run(conn, processor, report).await
+			}
+			else {
+				processor.process(conn).await
+			}
+		},
+		Command::Documents { state, concurrency } => processor_builder(&cli.sources, archive, cli.warc_output, &state, concurrency)?.build()?.documents(conn).await,
+		Command::Daemon { state, concurrency } => daemon::run(conn, &cli.sources, archive, cli.warc_output, &state, concurrency).await,
+		Command::Query { filter } => query(conn, &filter),
+		Command::Export { filter, format, output } => export(conn, &filter, format, output),
+		Command::Status => status(conn),
 	}
 }
 
-fn get_page_incrementer(state: &dto::State) -> Box<dyn Fn(i32) -> i32 + Send> {
-	match state {
-		dto::State::WA => Box::new(|page| page + 1),
-		dto::State::CA => Box::new(|_| 0),
-		dto::State::OR => Box::new(|_| 0),
-		dto::State::HI => Box::new(|_| 0),
-		dto::State::MD => Box::new(|page| page + 30),
-	}
-}
+fn processor_builder(sources_file: &Path, archive: Option<Archive>, warc: Option<PathBuf>, states: &[dto::State], concurrency: usize) -> Result<ProcessorBuilder> {
+	let config = Config::load(sources_file)?;
+
+	let mut builder = ProcessorBuilder::new()
+		.sources(sources::registered_for(&config, states)?)
+		.max_concurrency(concurrency)
+		.politeness(config.politeness.clone())
+		.http(config.http.clone());
 
-fn get_parser(state: &dto::State) -> Box<dyn Parser + Send> {
-	match state {
-		dto::State::WA => Box::new(WaParser{}),
-		dto::State::CA => Box::new(CaParser{}),
-		dto::State::OR => Box::new(OrParser{}),
-		dto::State::HI => Box::new(HiParser{}),
-		dto::State::MD => Box::new(MdParser{}),
+	if let Some(archive) = archive {
+		builder = builder.archive(archive);
 	}
-}
 
-fn get_retriever(state: &dto::State) -> Box<dyn Retriever> {
-	match state {
-		dto::State::WA => Box::new(MultiPage{}),
-		dto::State::CA => Box::new(SinglePage{}),
-		dto::State::OR => Box::new(SinglePage{}),
-		dto::State::HI => Box::new(SinglePage{}),
-		dto::State::MD => Box::new(MultiPage{}),
+	if let Some(warc) = warc {
+		builder = builder.warc(warc);
 	}
+
+	Ok(builder)
 }
 
-fn get_wa_options(conn: &mut SqliteConnection) -> RetrieverOptions {
-	let last_recieved = get_last_retrieved(conn, dto::State::WA.into()).unwrap();
+async fn dry_run(conn: &mut SqliteConnection, processor: Processor, format: ReportFormat) -> Result<()> {
+	let report = processor.dry_run(conn).await?;
 
-	retrievers::RetrieverOptions {
-		collect_until: match last_recieved {
-			Some(lr) => lr.retrieved_date.checked_sub_days(Days::new(1)).unwrap(),
-			None => NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
+	match format {
+		ReportFormat::Text => print!("{}", report),
+		ReportFormat::Json => {
+			let json = serde_json::to_string_pretty(&report).map_err(|err| Error::Io { context: "could not serialize dry run report".to_string(), source: err.into() })?;
+			println!("{}", json);
 		},
-		base_url: "https://www.atg.wa.gov/data-breach-notifications?page=".to_string(),
-		headers: create_headers(dto::State::WA),
-		state: dto::State::WA,
-		request_type: WebRequestType::Get,
 	}
+
+	Ok(())
 }
 
-fn get_ca_options(conn: &mut SqliteConnection) -> RetrieverOptions {
-	let last_recieved = get_last_retrieved(conn, dto::State::CA.into()).unwrap();
+fn filter_breaches(conn: &mut SqliteConnection, filter: &BreachFilter) -> Result<Vec<Breach>> {
+	let mut breaches: Vec<Breach> = get_breaches(conn)?.into_iter().filter(|breach| filter.matches(breach)).collect();
+	breaches.sort_by_key(|breach| std::cmp::Reverse(breach.date_reported));
 
-	retrievers::RetrieverOptions {
-		collect_until: match last_recieved {
-			Some(lr) => lr.retrieved_date.checked_sub_days(Days::new(1)).unwrap(),
-			None => NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
-		},
-		base_url: "https://oag.ca.gov/privacy/databreach/list".to_string(),
-		headers: create_headers(dto::State::CA),
-		state: dto::State::CA,
-		request_type: WebRequestType::Get,
+	if let Some(limit) = filter.limit {
+		breaches.truncate(limit);
 	}
+
+	Ok(breaches)
 }
 
-fn get_or_options(conn: &mut SqliteConnection) -> RetrieverOptions {
-	let last_recieved = get_last_retrieved(conn, dto::State::OR.into()).unwrap();
+fn query(conn: &mut SqliteConnection, filter: &BreachFilter) -> Result<()> {
+	let breaches = filter_breaches(conn, filter)?;
 
-	retrievers::RetrieverOptions {
-		collect_until: match last_recieved {
-			Some(lr) => lr.retrieved_date.checked_sub_days(Days::new(1)).unwrap(),
-			None => NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
-		},
-		base_url: "https://justice.oregon.gov/consumer/databreach/".to_string(),
-		headers: create_headers(dto::State::OR),
-		state: dto::State::OR,
-		request_type: WebRequestType::Get,
+	for breach in &breaches {
+		println!("{}\t{:?}\t{}\t{}",
+			breach.date_reported.date(),
+			breach.loc,
+			breach.affected_count_local.or(breach.affected_count).map(|c| c.to_string()).unwrap_or_else(|| "?".to_string()),
+			breach.organization_name);
 	}
-}
 
-fn get_hi_options(conn: &mut SqliteConnection) -> RetrieverOptions {
-	let last_recieved = get_last_retrieved(conn, dto::State::HI.into()).unwrap();
+	println!("{} breach(es)", breaches.len());
 
-	retrievers::RetrieverOptions {
-		collect_until: match last_recieved {
-			Some(lr) => lr.retrieved_date.checked_sub_days(Days::new(1)).unwrap(),
-			None => NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
-		},
-		base_url: "https://cca.hawaii.gov/ocp/notices/security-breach/".to_string(),
-		headers: create_headers(dto::State::HI),
-		state: dto::State::HI,
-		request_type: WebRequestType::Get,
-	}
+	Ok(())
 }
 
-fn get_md_options(conn: &mut SqliteConnection) -> Vec<RetrieverOptions> {
-	let mut opts = vec!();
-	let last_recieved = get_last_retrieved(conn, dto::State::MD.into()).unwrap();
+fn export(conn: &mut SqliteConnection, filter: &BreachFilter, format: ExportFormat, output: Option<PathBuf>) -> Result<()> {
+	let breaches = filter_breaches(conn, filter)?;
 
-	opts.push(retrievers::RetrieverOptions {
-		collect_until: match last_recieved {
-			Some(lr) => lr.retrieved_date.checked_sub_days(Days::new(1)).unwrap(),
-			None => NaiveDate::from_ymd_opt(-1, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
-		},
-		base_url: "https://www.marylandattorneygeneral.gov/_layouts/15/inplview.aspx?List=%7B04EBF6F4-B351-492F-B96D-167E2DE39C85%7D&View=%7BAC628F51-0774-4B71-A77E-77D6B9909F7E%7D&ViewCount=23&IsXslView=TRUE&IsCSR=TRUE&ListViewPageUrl=https%3A%2F%2Fwww.marylandattorneygeneral.gov%2Fpages%2Fidentitytheft%2Fbreachnotices.aspx&GroupString=%3B%23%3B%23&IsGroupRender=TRUE&WebPartID={AC628F51-0774-4B71-A77E-77D6B9909F7E}".to_string(),
-		headers: create_headers(dto::State::MD),
-		state: dto::State::MD,
-		request_type: WebRequestType::Post,
-	});
-
-	opts.push(retrievers::RetrieverOptions {
-		collect_until: match last_recieved {
-			Some(lr) => lr.retrieved_date.checked_sub_days(Days::new(1)).unwrap(),
-			None => NaiveDate::from_ymd_opt(-1, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
-		},
-		base_url: "https://www.marylandattorneygeneral.gov/_layouts/15/inplview.aspx?List=%7B04EBF6F4-B351-492F-B96D-167E2DE39C85%7D&View=%7BAC628F51-0774-4B71-A77E-77D6B9909F7E%7D&ViewCount=23&IsXslView=TRUE&IsCSR=TRUE&ListViewPageUrl=https%3A%2F%2Fwww.marylandattorneygeneral.gov%2Fpages%2Fidentitytheft%2Fbreachnotices.aspx&GroupString=%3B%232020%3B%23&IsGroupRender=TRUE&WebPartID={AC628F51-0774-4B71-A77E-77D6B9909F7E}".to_string(),
-		headers: create_headers(dto::State::MD),
-		state: dto::State::MD,
-		request_type: WebRequestType::Post,
-	});
-
-	opts.push(retrievers::RetrieverOptions {
-		collect_until: match last_recieved {
-			Some(lr) => lr.retrieved_date.checked_sub_days(Days::new(1)).unwrap(),
-			None => NaiveDate::from_ymd_opt(-1, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
-		},
-		base_url: "https://www.marylandattorneygeneral.gov/_layouts/15/inplview.aspx?List=%7B04EBF6F4-B351-492F-B96D-167E2DE39C85%7D&View=%7BAC628F51-0774-4B71-A77E-77D6B9909F7E%7D&ViewCount=23&IsXslView=TRUE&IsCSR=TRUE&ListViewPageUrl=https%3A%2F%2Fwww.marylandattorneygeneral.gov%2Fpages%2Fidentitytheft%2Fbreachnotices.aspx&GroupString=%3B%232021%3B%23&IsGroupRender=TRUE&WebPartID={AC628F51-0774-4B71-A77E-77D6B9909F7E}".to_string(),
-		headers: create_headers(dto::State::MD),
-		state: dto::State::MD,
-		request_type: WebRequestType::Post,
-	});
-
-	opts.push(retrievers::RetrieverOptions {
-		collect_until: match last_recieved {
-			Some(lr) => lr.retrieved_date.checked_sub_days(Days::new(1)).unwrap(),
-			None => NaiveDate::from_ymd_opt(-1, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()
-		},
-		base_url: "https://www.marylandattorneygeneral.gov/_layouts/15/inplview.aspx?List=%7B04EBF6F4-B351-492F-B96D-167E2DE39C85%7D&View=%7BAC628F51-0774-4B71-A77E-77D6B9909F7E%7D&ViewCount=23&IsXslView=TRUE&IsCSR=TRUE&ListViewPageUrl=https%3A%2F%2Fwww.marylandattorneygeneral.gov%2Fpages%2Fidentitytheft%2Fbreachnotices.aspx&GroupString=%3B%232022%3B%23&IsGroupRender=TRUE&WebPartID={AC628F51-0774-4B71-A77E-77D6B9909F7E}".to_string(),
-		headers: create_headers(dto::State::MD),
-		state: dto::State::MD,
-		request_type: WebRequestType::Post,
-	});
+	let context = match &output {
+		Some(path) => format!("could not write {}", path.display()),
+		None => "could not write to stdout".to_string(),
+	};
+	let mut writer: Box<dyn Write> = match &output {
+		Some(path) => Box::new(File::create(path).map_err(|source| Error::Io { context: context.clone(), source })?),
+		None => Box::new(io::stdout()),
+	};
 
-	opts
-}
+	let result = match format {
+		ExportFormat::Json => serde_json::to_writer_pretty(&mut writer, &breaches).map_err(io::Error::from).and_then(|_| writeln!(writer)),
+		ExportFormat::Csv => write_csv(&mut writer, &breaches),
+	};
 
-pub struct Processor {
-	to_process: Vec<RetrieverOptions>,
+	result.and_then(|_| writer.flush()).map_err(|source| Error::Io { context, source })
 }
 
-impl Processor {
-	pub async fn process(&self, conn: &mut SqliteConnection) -> Result<(), Box<dyn std::error::Error>> {
-		for options in self.to_process.iter() {
-			if let Err(err) = self.process_breaches(conn, options).await {
-				println!("{:?}", err)
-			}
+fn write_csv(writer: &mut dyn Write, breaches: &[Breach]) -> io::Result<()> {
+	fn escape(value: &str) -> String {
+		if value.contains(',') || value.contains('"') || value.contains('\n') {
+			return format!("\"{}\"", value.replace('"', "\"\""));
 		}
 
-		Ok(())
+		value.to_string()
 	}
 
-	async fn process_breaches(&self, conn: &mut SqliteConnection, options: &retrievers::RetrieverOptions) -> Result<(), Box<dyn std::error::Error>> {
-		let rec = get_retriever(&options.state);
+	fn optional<T: ToString>(value: &Option<T>) -> String {
+		value.as_ref().map(|v| v.to_string()).unwrap_or_default()
+	}
 
-		let client = reqwest::Client::new();
+	writeln!(writer, "id,state,date_reported,organization_name,date_of_breach,affected_count,affected_count_local,breach_type,link,leaked_info")?;
+
+	for breach in breaches {
+		let leaked_info = breach.leaked_info.iter().map(|class| match class {
+			dto::ClassificationType::Unknown(content, _) => content.clone(),
+			known => format!("{:?}", datamodels::ClassificationType::from(known)),
+		}).collect::<Vec<String>>().join(";");
+
+		writeln!(writer, "{},{:?},{},{},{},{},{},{:?},{},{}",
+			breach.id,
+			breach.loc,
+			breach.date_reported.date(),
+			escape(&breach.organization_name),
+			optional(&breach.date_of_breach.map(|d| d.date())),
+			optional(&breach.affected_count),
+			optional(&breach.affected_count_local),
+			breach.breach_type,
+			escape(&optional(&breach.link)),
+			escape(&leaked_info))?;
+	}
 
-		let breaches = rec.retrieve(&client, get_parser(&options.state), &options, get_page_incrementer(&options.state), get_url_generator(&options.state)).await?;
+	Ok(())
+}
 
-		if breaches.len() > 0 {
-			let mut inserted_breaches_count = 0;
-			for breach in &breaches {
-				let res = create_breach_data(conn, &breach);
+fn status(conn: &mut SqliteConnection) -> Result<()> {
+	let now = Utc::now().naive_utc();
 
-				if let Ok((i, c)) = res {
-					inserted_breaches_count += i;
-					if i == 0 && c > 0 {
-						println!("Created {} new classification(s) for {:?}", c, breach);
-					}
-				}
-				else {
-					println!("Error storing {:?}: {:?}", breach, res);
-				}
-			}
+	for state in dto::State::all() {
+		let last_success = get_last_scrape_run(conn, state.into(), &[RunStatus::Succeeded, RunStatus::Unchanged]).map_err(Error::storage(state))?;
+		let last_run = get_last_scrape_run(conn, state.into(), &[]).map_err(Error::storage(state))?;
+		let last_retrieved = get_last_retrieved(conn, state.into()).map_err(Error::storage(state))?;
 
-			println!("Inserted total of {} breaches", inserted_breaches_count);
+		let retrieved = match last_retrieved {
+			Some(lr) => format!("last retrieved {}", lr.retrieved_date.date()),
+			None => "never retrieved".to_string(),
+		};
 
-			if inserted_breaches_count > 0 {
-				let last_retrieved = NewLastRetrieved {
-					loc: options.state.into(),
-					retrieved_date: breaches.first().unwrap().date_reported
-				};
+		let success = match last_success.as_ref().and_then(|run| run.finished_at) {
+			Some(finished_at) => format!("last success {} ({} days ago)", finished_at.format("%Y-%m-%d %H:%M"), (now - finished_at).num_days()),
+			None => "never succeeded".to_string(),
+		};
 
-				let lr_result = insert_last_retrieved(conn, last_retrieved);
+		println!("{:?}\t{}\t{}", state, retrieved, success);
 
-				println!("{}", json!(lr_result));
-			}
-			else {
-				println!("No new breaches to insert in {:?}", options.state);
+		if let Some(run) = last_run {
+			match run.run_status {
+				RunStatus::Failed => println!("\tlast run {} failed ({}) with {} error(s): {}", run.started_at.format("%Y-%m-%d %H:%M"), run.error_kind.as_deref().unwrap_or("unknown"), run.error_count, run.errors.unwrap_or_default()),
+				RunStatus::Running => println!("\tlast run {} did not finish", run.started_at.format("%Y-%m-%d %H:%M")),
+				RunStatus::Succeeded => println!("\tlast run fetched {} page(s), parsed {} and inserted {} breach(es)", run.pages_fetched, run.rows_parsed, run.rows_inserted),
+				RunStatus::Unchanged => println!("\tlast run {} found nothing changed since the run before", run.started_at.format("%Y-%m-%d %H:%M")),
 			}
 		}
-		else {
-			println!("No new breaches to insert in {:?}", options.state);
-		}
-
-		Ok(())
 	}
-}
 
-pub struct ProcessorBuilder {
-	to_process: Vec<RetrieverOptions>
+	Ok(())
 }
-
-impl ProcessorBuilder {
-	pub fn new() -> Self {
-		Self {
-			to_process: vec!()
-		}
-	}
-
-	pub fn process_option(mut self, option: RetrieverOptions) -> ProcessorBuilder {
-		self.to_process.push(option);
-		self
-	}
-
-	pub fn process_options(mut self, options: Vec<RetrieverOptions>) -> ProcessorBuilder {
-		for opt in options {
-			self.to_process.push(opt);
-		}
-
-		self
-	}
-
-	pub fn build(self) -> Result<Processor, String> {
-		if self.to_process.len() == 0 {
-			return Err("Cannot create processor without any options".to_string())
-		}
-
-		Ok(Processor {
-			to_process: self.to_process
-		})
-	}
-}
\ No newline at end of file
@@ -1,11 +1,11 @@
 use chrono::NaiveDateTime;
 use crate::dto::{Breach, State, BreachType};
-use super::Parser;
+use super::{Parser, ParseError};
 
 pub struct CaParser { }
 
 impl CaParser {
-	fn parse_body(text: &str) -> Result<(Vec<Breach>, Option<String>), Box<dyn std::error::Error>> {
+	fn parse_body(text: &str) -> Result<(Vec<Breach>, Option<String>), ParseError> {
 		let mut content_it = text.split("<tbody>");
 		_ = content_it.next();
 		let content = content_it.next();
@@ -19,8 +19,8 @@ impl CaParser {
 					let mut row_it = rows.split("<tr ");
 					_ = row_it.next();
 
-					while let Some(row) = row_it.next() {
-						let mut breach = CaParser::parse_breach(row)?;
+					for (i, row) in row_it.enumerate() {
+						let mut breach = CaParser::parse_breach(i, row)?;
 						breaches.append(&mut breach);
 					}
 				}
@@ -30,7 +30,7 @@ impl CaParser {
 		Ok((breaches, None))
 	}
 
-	fn parse_breach(text: &str) -> Result<Vec<Breach>, Box<dyn std::error::Error>> {
+	fn parse_breach(row: usize, text: &str) -> Result<Vec<Breach>, ParseError> {
 		let mut row_it = text.split("</td>");
 
 		let mut date_of_breaches: Vec<Option<NaiveDateTime>> = vec!();
@@ -45,16 +45,16 @@ impl CaParser {
 
 			// this is the point of the link
 			{
-				let a = field_it.next().unwrap();
+				let a = field_it.next().ok_or_else(|| ParseError::row(row, "missing organization link", text))?;
 				let mut link_href_it = a.split("href=\"");
 				_ = link_href_it.next();
 
-				let link_cont = link_href_it.next().unwrap();
+				let link_cont = link_href_it.next().ok_or_else(|| ParseError::row(row, "missing href in organization link", text))?;
 				let mut link_cont_it = link_cont.split("\"");
 				link = Some(link_cont_it.next().unwrap().to_string());
 			}
 
-			let org = field_it.next().unwrap();
+			let org = field_it.next().ok_or_else(|| ParseError::row(row, "missing organization name", text))?;
 			let mut org_it = org.split("<");
 			organization_name = Some(org_it.next().unwrap().trim());
 		}
@@ -72,10 +72,10 @@ impl CaParser {
 					let mut content_it = date_str.split("content=\"");
 					let _ = content_it.next();
 
-					while let Some(content) = content_it.next() {
+					for content in content_it {
 						let mut date_it = content.split("\"");
 						let date = date_it.next().unwrap();
-						date_of_breaches.push(Some(NaiveDateTime::parse_from_str(&date, "%+").unwrap()));
+						date_of_breaches.push(Some(NaiveDateTime::parse_from_str(date, "%+").map_err(|err| ParseError::row(row, format!("invalid date: {}", err), text))?));
 					}
 				}
 			}
@@ -88,20 +88,20 @@ impl CaParser {
 			let mut field_it = field.split(">");
 			_ = field_it.next();
 
-			let date_str = field_it.next().unwrap();
+			let date_str = field_it.next().ok_or_else(|| ParseError::row(row, "missing date reported", text))?;
 			let mut date_str_it = date_str.split("<");
 			let b = date_str_it.next().unwrap().trim();
-			date_reported = Some(NaiveDateTime::parse_from_str(&(b.to_string() + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").unwrap());
+			date_reported = Some(NaiveDateTime::parse_from_str(&(b.to_string() + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").map_err(|err| ParseError::row(row, format!("invalid date: {}", err), text))?);
 		}
 
-		if date_reported.is_none() || date_of_breaches.len() == 0 || organization_name.is_none() {
-			return Err(format!("CA parsing failure {}", text).into());
+		if date_reported.is_none() || date_of_breaches.is_empty() || organization_name.is_none() {
+			return Err(ParseError::row(row, "missing date reported, date of breach or organization name", text));
 		}
 
 		Ok(date_of_breaches.iter().map(|dob| Breach {
 			id: 0,
 			date_reported: date_reported.unwrap(),
-			date_of_breach: dob.clone(),
+			date_of_breach: *dob,
 			organization_name: organization_name.unwrap().to_string(),
 			affected_count: None,
 			affected_count_local: None,
@@ -114,7 +114,7 @@ impl CaParser {
 }
 
 impl Parser for CaParser {
-	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), Box<dyn std::error::Error>> {
+	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), ParseError> {
 		CaParser::parse_body(page)
 	}
 }
\ No newline at end of file
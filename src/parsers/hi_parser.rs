@@ -1,11 +1,11 @@
 use chrono::NaiveDateTime;
 use crate::dto::{Breach, State, BreachType};
-use super::Parser;
+use super::{Parser, ParseError};
 
 pub struct HiParser { }
 
 impl HiParser {
-	fn parse_body(text: &str) -> Result<(Vec<Breach>, Option<String>), Box<dyn std::error::Error>> {
+	fn parse_body(text: &str) -> Result<(Vec<Breach>, Option<String>), ParseError> {
 		let mut content_it = text.split("<tbody class=\"row-hover\">");
 		_ = content_it.next();
 		let content = content_it.next();
@@ -19,8 +19,8 @@ impl HiParser {
 					let mut row_it = rows.split("<tr ");
 					_ = row_it.next();
 
-					while let Some(row) = row_it.next() {
-						let breach = HiParser::parse_breach(row)?;
+					for (i, row) in row_it.enumerate() {
+						let breach = HiParser::parse_breach(i, row)?;
 						breaches.push(breach);
 					}
 				}
@@ -30,7 +30,7 @@ impl HiParser {
 		Ok((breaches, None))
 	}
 
-	fn parse_breach(text: &str) -> Result<Breach, Box<dyn std::error::Error>> {
+	fn parse_breach(row: usize, text: &str) -> Result<Breach, ParseError> {
 		let mut row_it = text.split("</td>");
 
 		let mut date_reported = None;
@@ -54,10 +54,10 @@ impl HiParser {
 			_ = field_it.next(); // removes up to end of tr
 			_ = field_it.next(); // removes up to start of td value
 
-			let dr = field_it.next().unwrap(); // gets date_reported value include </td
+			let dr = field_it.next().ok_or_else(|| ParseError::row(row, "missing date reported", text))?; // gets date_reported value include </td
 			let date = dr.trim().chars().take(10).collect::<String>();
 
-			date_reported = Some(NaiveDateTime::parse_from_str(&(date + " 00:00:00 +00:00"), "%Y/%m.%d %H:%M:%S %z").unwrap());
+			date_reported = Some(NaiveDateTime::parse_from_str(&(date + " 00:00:00 +00:00"), "%Y/%m.%d %H:%M:%S %z").map_err(|err| ParseError::row(row, format!("invalid date: {}", err), text))?);
 		}
 
 		_ = row_it.next(); // skips case_number
@@ -103,10 +103,7 @@ impl HiParser {
 				let mut count_it = count.split("<");
 				let count = count_it.next().unwrap();
 
-				affected_count = match count.trim().replace(",", "").parse::<i32>() {
-					Ok(c) => Some(c),
-					_ => None
-				}
+				affected_count = count.trim().replace(",", "").parse::<i32>().ok()
 			}
 		}
 
@@ -115,7 +112,7 @@ impl HiParser {
 			_ = field_it.next();
 
 			// link starts here
-			let a = field_it.next().unwrap();
+			let a = field_it.next().ok_or_else(|| ParseError::row(row, "missing link", text))?;
 			let mut href_it = a.split("href=\"");
 			let _ = href_it.next();
 
@@ -130,7 +127,7 @@ impl HiParser {
 		}
 
 		if date_reported.is_none() || organization_name.is_none() {
-			return Err(format!("HI parsing failure {}", text).into());
+			return Err(ParseError::row(row, "missing date reported or organization name", text));
 		}
 
 		Ok(Breach {
@@ -149,7 +146,7 @@ impl HiParser {
 }
 
 impl Parser for HiParser {
-	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), Box<dyn std::error::Error>> {
+	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), ParseError> {
 		HiParser::parse_body(page)
 	}
 }
\ No newline at end of file
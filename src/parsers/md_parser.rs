@@ -1,7 +1,7 @@
 use chrono::{NaiveDateTime};
 use serde::{Serialize, Deserialize};
 use crate::dto::{Breach, State, ClassificationType, BreachType, Sensitivity};
-use super::Parser;
+use super::{Parser, ParseError, sharepoint::ListView};
 
 const FILE_BASE_URI: &str = "https://www.marylandattorneygeneral.gov/";
 
@@ -21,37 +21,27 @@ struct BreachData {
 	pub how_x0020_breach_x0020_occurred: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Response {
-	#[serde(rename = "Row")]
-	rows: Vec<BreachData>,
-	#[serde(rename = "NextHref")]
-	next_url: Option<String>,
-}
-
 pub struct MdParser { }
 
 impl MdParser {
-	fn parse_body(text: &str) -> Result<(Vec<Breach>, Option<String>), Box<dyn std::error::Error>> {
-		let des = serde_json::from_str::<Response>(text).unwrap();
+	fn parse_body(text: &str) -> Result<(Vec<Breach>, Option<String>), ParseError> {
+		let des = ListView::<BreachData>::parse(text)?;
+		let next_url = des.next_url_part()?;
 
 		let mut breaches = vec!();
 
-		for record in des.rows {
-			if record.date_x0020_received == "" {
+		for (row, record) in des.rows.into_iter().enumerate() {
+			if record.date_x0020_received.is_empty() {
 				breaches.push(Breach {
 					id: 0,
 					date_reported: NaiveDateTime::parse_from_str("01/01/0001 00:00:00 -00:00", "%m/%d/%Y %H:%M:%S %z").unwrap(),
 					date_of_breach: None,
 					organization_name: record.case_x0020_title.clone(),
-					affected_count_local: match record.no_x0020_of_x0020_md_x0020_residents.trim().replace(",", "").parse::<i32>() {
-						Ok(c) => Some(c),
-						_ => None
-					},
+					affected_count_local: record.no_x0020_of_x0020_md_x0020_residents.trim().replace(",", "").parse::<i32>().ok(),
 					affected_count: None,
 					loc: State::MD,
 					breach_type: MdParser::parse_breach_type(&record.how_x0020_breach_x0020_occurred),
-					link: Some(format!("{}{}", FILE_BASE_URI, record.file_ref.replace("\u{002f}", "/"))),
+					link: Some(format!("{}{}", FILE_BASE_URI, record.file_ref)),
 					leaked_info: MdParser::parse_leaked_info(&record.information_x0020_breached)
 				});
 				continue;
@@ -59,44 +49,28 @@ impl MdParser {
 
 			breaches.push(Breach {
 				id: 0,
-				date_reported: NaiveDateTime::parse_from_str(&format!("{} 00:00:00 -00:00", record.date_x0020_received.replace("\u{002f}", "/")), "%m/%d/%Y %H:%M:%S %z").unwrap(), // in mm/dd/yyyy format, but / are \u002f
+				date_reported: NaiveDateTime::parse_from_str(&format!("{} 00:00:00 -00:00", record.date_x0020_received), "%m/%d/%Y %H:%M:%S %z") // in mm/dd/yyyy format, the / sent as \u002f are unescaped by the JSON parser
+					.map_err(|err| ParseError::row(row, format!("invalid date received: {}", err), &record.date_x0020_received))?,
 				date_of_breach: None,
 				organization_name: record.case_x0020_title.clone(),
-				affected_count_local: match record.no_x0020_of_x0020_md_x0020_residents.trim().replace(",", "").parse::<i32>() {
-					Ok(c) => Some(c),
-					_ => None
-				},
+				affected_count_local: record.no_x0020_of_x0020_md_x0020_residents.trim().replace(",", "").parse::<i32>().ok(),
 				affected_count: None,
 				loc: State::MD,
 				breach_type: MdParser::parse_breach_type(&record.how_x0020_breach_x0020_occurred),
-				link: Some(format!("{}{}", FILE_BASE_URI, record.file_ref.replace("\u{002f}", "/"))),
+				link: Some(format!("{}{}", FILE_BASE_URI, record.file_ref)),
 				leaked_info: MdParser::parse_leaked_info(&record.information_x0020_breached)
 			})
 		}
 
-		if let Some(next_raw) = des.next_url {
-			let mut next_url = "&".to_string();
-			let mut url_it = next_raw.split("breachnotices.aspx?");
-			_ = url_it.next();
-
-			let to_add = url_it.next().unwrap();
-			let mut to_add_it = to_add.split("View=");
-
-			next_url.push_str(to_add_it.next().unwrap());
-
-			return Ok((breaches, Some(next_url)))
-		}
-
-		return Ok((breaches, None))
-
+		Ok((breaches, next_url))
 	}
 
 	fn parse_leaked_info(text: &str) -> Vec<ClassificationType> {
 		let cleaned = text.replace("and/or", ",").replace("and", ",").replace(".", "").replace("&quot;", "").replace("\"", "");
 		let mut classifications = vec!();
-		let mut class_it = cleaned.split(",");
+		let class_it = cleaned.split(",");
 
-		while let Some(class) = class_it.next() {
+		for class in class_it {
 			let trimmed = class.trim().to_lowercase();
 			let mut added = false;
 
@@ -237,7 +211,7 @@ impl MdParser {
 }
 
 impl Parser for MdParser {
-	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), Box<dyn std::error::Error>> {
+	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), ParseError> {
 		MdParser::parse_body(page)
 	}
 }
@@ -1,4 +1,3 @@
-use std::error::Error;
 use crate::dto::Breach;
 
 pub mod wa_parser;
@@ -6,7 +5,37 @@ pub mod or_parser;
 pub mod ca_parser;
 pub mod md_parser;
 pub mod hi_parser;
+pub mod sharepoint;
+
+const SNIPPET_LENGTH: usize = 200;
 
 pub trait Parser {
-	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), Box<dyn Error>>;
+	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), ParseError>;
+}
+
+/// Raised when a page does not look the way a parser expects, which usually means the site's layout changed
+#[derive(Debug, thiserror::Error)]
+#[error("{message}{} near \"{snippet}\"", row.map(|r| format!(" in row {}", r)).unwrap_or_default())]
+pub struct ParseError {
+	pub message: String,
+	pub row: Option<usize>,
+	pub snippet: String,
+}
+
+impl ParseError {
+	pub fn new(message: impl Into<String>, raw: &str) -> Self {
+		ParseError { message: message.into(), row: None, snippet: snippet(raw) }
+	}
+
+	pub fn row(row: usize, message: impl Into<String>, raw: &str) -> Self {
+		ParseError { message: message.into(), row: Some(row), snippet: snippet(raw) }
+	}
+}
+
+fn snippet(raw: &str) -> String {
+	let trimmed = raw.trim();
+	match trimmed.char_indices().nth(SNIPPET_LENGTH) {
+		Some((end, _)) => format!("{}...", &trimmed[..end]),
+		None => trimmed.to_string(),
+	}
 }
@@ -1,11 +1,11 @@
 use chrono::NaiveDateTime;
 use crate::dto::{Breach, State, BreachType};
-use super::Parser;
+use super::{Parser, ParseError};
 
 pub struct OrParser { }
 
 impl OrParser {
-	fn parse_body(text: &str) -> Result<(Vec<Breach>, Option<String>), Box<dyn std::error::Error>> {
+	fn parse_body(text: &str) -> Result<(Vec<Breach>, Option<String>), ParseError> {
 		let mut content_it = text.split("<tbody>");
 		_ = content_it.next();
 		let content = content_it.next();
@@ -19,8 +19,8 @@ impl OrParser {
 					let mut row_it = rows.split("<tr>");
 					_ = row_it.next();
 
-					while let Some(row) = row_it.next() {
-						let mut breach = OrParser::parse_breach(row)?;
+					for (i, row) in row_it.enumerate() {
+						let mut breach = OrParser::parse_breach(i, row)?;
 						breaches.append(&mut breach);
 					}
 				}
@@ -30,7 +30,7 @@ impl OrParser {
 		Ok((breaches, None))
 	}
 
-	fn parse_breach(text: &str) -> Result<Vec<Breach>, Box<dyn std::error::Error>> {
+	fn parse_breach(row: usize, text: &str) -> Result<Vec<Breach>, ParseError> {
 		let mut row_it = text.split("</td>");
 
 		let mut date_of_breaches: Vec<Option<NaiveDateTime>> = vec!();
@@ -44,18 +44,18 @@ impl OrParser {
 
 			// link starts here
 			{
-				let a = field_it.next().unwrap();
+				let a = field_it.next().ok_or_else(|| ParseError::row(row, "missing organization link", text))?;
 				let mut href_it = a.split("href=\"");
 				let _ = href_it.next();
 
-				let link_raw = href_it.next().unwrap();
+				let link_raw = href_it.next().ok_or_else(|| ParseError::row(row, "missing href in organization link", text))?;
 				let mut link_raw_it = link_raw.split("\"");
 				let l = link_raw_it.next().unwrap().to_string();
 
 				link = Some(format!("{}{}", "https://justice.oregon.gov", l));
 			}
 
-			let org = field_it.next().unwrap();
+			let org = field_it.next().ok_or_else(|| ParseError::row(row, "missing organization name", text))?;
 			let mut org_it = org.split("<");
 			organization_name = Some(org_it.next().unwrap().trim());
 		}
@@ -69,17 +69,17 @@ impl OrParser {
 				let mut date_str_it = date_str.split("<");
 				let b = date_str_it.next().unwrap().trim();
 
-				if b == "" {
+				if b.is_empty() {
 					date_of_breaches.push(None);
 				}
 				else {
 					if b.contains(",") {
-						let mut b_it = b.split(",");
+						let b_it = b.split(",");
 
-						while let Some(one_date) = b_it.next() {
+						for one_date in b_it {
 							if one_date.len() >= 10 {
 								let date = one_date.trim().chars().take(10).collect::<String>();
-								date_of_breaches.push(Some(NaiveDateTime::parse_from_str(&(date + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").unwrap()));
+								date_of_breaches.push(Some(NaiveDateTime::parse_from_str(&(date + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").map_err(|err| ParseError::row(row, format!("invalid date: {}", err), text))?));
 							}
 							else if one_date.len() < 10 {
 								date_of_breaches.push(None);
@@ -88,7 +88,7 @@ impl OrParser {
 					}
 					else {
 						let date = b.chars().take(10).collect::<String>();
-						date_of_breaches.push(Some(NaiveDateTime::parse_from_str(&(date + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").unwrap()));
+						date_of_breaches.push(Some(NaiveDateTime::parse_from_str(&(date + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").map_err(|err| ParseError::row(row, format!("invalid date: {}", err), text))?));
 					}
 				}
 			}
@@ -101,20 +101,20 @@ impl OrParser {
 			let mut field_it = field.split(">");
 			_ = field_it.next();
 
-			let date_str = field_it.next().unwrap();
+			let date_str = field_it.next().ok_or_else(|| ParseError::row(row, "missing date reported", text))?;
 			let mut date_str_it = date_str.split("<");
 			let b = date_str_it.next().unwrap().trim();
-			date_reported = Some(NaiveDateTime::parse_from_str(&(b.to_string() + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").unwrap());
+			date_reported = Some(NaiveDateTime::parse_from_str(&(b.to_string() + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").map_err(|err| ParseError::row(row, format!("invalid date: {}", err), text))?);
 		}
 
-		if date_reported.is_none() || date_of_breaches.len() == 0 || organization_name.is_none() {
-			return Err(format!("OR parsing failure {}", text).into());
+		if date_reported.is_none() || date_of_breaches.is_empty() || organization_name.is_none() {
+			return Err(ParseError::row(row, "missing date reported, date of breach or organization name", text));
 		}
 
 		Ok(date_of_breaches.iter().map(|dob| Breach {
 			id: 0,
 			date_reported: date_reported.unwrap(),
-			date_of_breach: dob.clone(),
+			date_of_breach: *dob,
 			organization_name: organization_name.unwrap().to_string(),
 			affected_count_local: None,
 			affected_count: None,
@@ -127,7 +127,7 @@ impl OrParser {
 }
 
 impl Parser for OrParser {
-	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), Box<dyn std::error::Error>> {
+	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), ParseError> {
 		OrParser::parse_body(page)
 	}
 }
\ No newline at end of file
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::Value;
+use super::ParseError;
+
+/// Response of a SharePoint list view request (`_layouts/15/inplview.aspx`), the rows of one page and the link to the
+/// next one
+#[derive(Debug, Deserialize)]
+pub struct ListView<T> {
+	#[serde(rename = "Row")]
+	pub rows: Vec<T>,
+	#[serde(rename = "NextHref")]
+	pub next_href: Option<String>,
+}
+
+impl<T: DeserializeOwned> ListView<T> {
+	pub fn parse(text: &str) -> Result<ListView<T>, ParseError> {
+		serde_json::from_str(text).map_err(|err| ParseError::new(format!("invalid list view response: {}", err), text))
+	}
+
+	/// Query of the NextHref, to be appended to the list view request for the next page. Everything from the view on is
+	/// left out as the request already names it.
+	pub fn next_url_part(&self) -> Result<Option<String>, ParseError> {
+		let Some(next_href) = &self.next_href else { return Ok(None) };
+		let (_, query) = next_href.split_once('?').ok_or_else(|| ParseError::new("unexpected NextHref", next_href))?;
+		let query = query.split("View=").next().unwrap_or_default();
+
+		Ok(Some(format!("&{}", query)))
+	}
+}
+
+/// Values of `field` in the rows of a collapsed list view, which has a row for every group, in the order they are
+/// listed along with the url part of the next page
+pub fn group_values(text: &str, field: &str) -> Result<(Vec<String>, Option<String>), ParseError> {
+	let view = ListView::<HashMap<String, Value>>::parse(text)?;
+	let mut groups = vec!();
+
+	for (row, values) in view.rows.iter().enumerate() {
+		let group = match values.get(field) {
+			Some(Value::String(value)) => value.clone(),
+			Some(Value::Number(value)) => value.to_string(),
+			Some(Value::Null) => String::new(),
+			_ => return Err(ParseError::row(row, format!("no {} value to group by", field), text)),
+		};
+
+		if !groups.contains(&group) {
+			groups.push(group);
+		}
+	}
+
+	Ok((groups, view.next_url_part()?))
+}
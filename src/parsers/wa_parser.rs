@@ -1,11 +1,11 @@
 use chrono::NaiveDateTime;
 use crate::dto::{ClassificationType, Sensitivity, Breach, State, BreachType};
-use super::Parser;
+use super::{Parser, ParseError};
 
 pub struct WaParser{}
 
 impl WaParser {
-	fn parse_body(text: &str) -> Result<(Vec<Breach>, Option<String>), Box<dyn std::error::Error>> {
+	fn parse_body(text: &str) -> Result<(Vec<Breach>, Option<String>), ParseError> {
 		let mut content_it = text.split("<tbody>");
 		_ = content_it.next();
 		_ = content_it.next();
@@ -20,8 +20,8 @@ impl WaParser {
 					let mut row_it = rows.split("<tr ");
 					_ = row_it.next();
 
-					while let Some(row) = row_it.next() {
-						let breach = WaParser::parse_breach(row)?;
+					for (i, row) in row_it.enumerate() {
+						let breach = WaParser::parse_breach(i, row)?;
 
 						breaches.push(breach);
 					}
@@ -32,7 +32,7 @@ impl WaParser {
 		Ok((breaches, None))
 	}
 
-	fn parse_breach(text: &str) -> Result<Breach, Box<dyn std::error::Error>> {
+	fn parse_breach(row: usize, text: &str) -> Result<Breach, ParseError> {
 		let mut row_it = text.split("</td>");
 
 		let mut date_reported = None;
@@ -48,10 +48,10 @@ impl WaParser {
 			_ = field_it.next();
 			_ = field_it.next();
 
-			let date_str = field_it.next().unwrap();
+			let date_str = field_it.next().ok_or_else(|| ParseError::row(row, "missing date reported", text))?;
 			let mut date_str_it = date_str.split("<");
 			let b = date_str_it.next().unwrap().trim();
-			date_reported = Some(NaiveDateTime::parse_from_str(&(b.to_string() + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").unwrap());
+			date_reported = Some(NaiveDateTime::parse_from_str(&(b.to_string() + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").map_err(|err| ParseError::row(row, format!("invalid date: {}", err), text))?);
 		}
 
 		if let Some(field) = row_it.next() {
@@ -60,18 +60,18 @@ impl WaParser {
 
 			// link starts here
 			{
-				let a = field_it.next().unwrap();
+				let a = field_it.next().ok_or_else(|| ParseError::row(row, "missing organization link", text))?;
 				let mut href_it = a.split("href=\"");
 				let _ = href_it.next();
 
-				let link_raw = href_it.next().unwrap();
+				let link_raw = href_it.next().ok_or_else(|| ParseError::row(row, "missing href in organization link", text))?;
 				let mut link_raw_it = link_raw.split("\"");
 				let l = link_raw_it.next().unwrap().to_string();
 
 				link = Some(l);
 			}
 
-			let org = field_it.next().unwrap();
+			let org = field_it.next().ok_or_else(|| ParseError::row(row, "missing organization name", text))?;
 			let mut org_it = org.split("<");
 			organization_name = Some(org_it.next().unwrap().trim());
 		}
@@ -85,7 +85,7 @@ impl WaParser {
 			if let Some(date_str) = date_str {
 				let mut date_str_it = date_str.split("<");
 				let b = date_str_it.next().unwrap().trim();
-				date_of_breach = Some(NaiveDateTime::parse_from_str(&(b.to_string() + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").unwrap());
+				date_of_breach = Some(NaiveDateTime::parse_from_str(&(b.to_string() + " 00:00:00 +00:00"), "%m/%d/%Y %H:%M:%S %z").map_err(|err| ParseError::row(row, format!("invalid date: {}", err), text))?);
 			}
 			else {
 				date_of_breach = None;
@@ -97,7 +97,7 @@ impl WaParser {
 			_ = field_it.next();
 			if let Some(count) = field_it.next() {
 				let count = count.trim();
-				if count == "Unknown" || count == "" {
+				if count == "Unknown" || count.is_empty() {
 					affected_count = Some(0);
 				}
 				else {
@@ -112,17 +112,17 @@ impl WaParser {
 		if let Some(field) = row_it.next() {
 			let mut field_it = field.split(">");
 			_ = field_it.next();
-			leaked_info = WaParser::parse_classifications(field_it.next().unwrap().trim());
+			leaked_info = WaParser::parse_classifications(field_it.next().ok_or_else(|| ParseError::row(row, "missing information compromised", text))?.trim());
 		}
 
 		if date_reported.is_none() || organization_name.is_none() || affected_count.is_none() {
-			return Err(format!("WA parsing failure {}", text).into());
+			return Err(ParseError::row(row, "missing date reported, organization name or affected count", text));
 		}
 
 		Ok(Breach {
 			id: 0,
 			date_reported: date_reported.unwrap(),
-			date_of_breach: date_of_breach,
+			date_of_breach,
 			organization_name: organization_name.unwrap().to_string(),
 			affected_count: None,
 			affected_count_local: affected_count,
@@ -135,9 +135,9 @@ impl WaParser {
 
 	fn parse_classifications(text: &str) -> Vec<ClassificationType> {
 		let mut classifications = vec!();
-		let mut classification_it = text.split(";");
+		let classification_it = text.split(";");
 
-		while let Some(classification) = classification_it.next() {
+		for classification in classification_it {
 			let classification = classification.trim();
 
 			match classification {
@@ -173,7 +173,7 @@ impl WaParser {
 }
 
 impl Parser for WaParser {
-	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), Box<dyn std::error::Error>> {
+	fn parse_page(&self, page: &str) -> Result<(Vec<Breach>, Option<String>), ParseError> {
 		WaParser::parse_body(page)
 	}
 }
\ No newline at end of file
@@ -0,0 +1,903 @@
+use std::{collections::HashMap, path::PathBuf, process, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{Connection, QueryResult, SqliteConnection};
+use futures::{StreamExt, stream};
+use reqwest::{Client, header::CONTENT_TYPE};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{data::{create_breach_data, find_breach, new_breach_data, insert_last_retrieved, get_last_retrieved, create_scrape_run, finish_scrape_run, get_backfill_checkpoints, create_backfill_checkpoint, update_backfill_checkpoint, delete_backfill_checkpoints, create_fetched_page, get_http_cache, save_http_cache, get_missing_notice_documents, save_notice_document, try_lock_source, renew_source_locks, unlock_source}, datamodels::{NewSourceLock, NewFetchedPage, NewNoticeDocument, HttpCache, NewLastRetrieved, NewScrapeRun, FinishedScrapeRun, RunStatus, BackfillCheckpoint, NewBackfillCheckpoint, BackfillProgress}, diff::{DryRunReport, StateDiff, diff_breaches}, dto::{Breach, State}, error::{Error, Result}, retrievers::{Retriever, Retrieval, RetrieverOptions, Validators, FetchError, PageSink, PageCursor, StoredPage, RawResponse, download, archive::{Archive, FetchedPage, PageRecorder, headers_json}, client::{ClientFactory, HttpConfig}, tls::TlsConfig, fetcher::Fetcher, replay::{Replay, Snapshots}, warc::WarcWriter, politeness::{Hosts, PolitenessPolicy}}, sources::Source};
+
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+/// Locks are renewed with every stored page, a lock that was not renewed for this long belongs to a run that died and
+/// is taken over
+const LOCK_TTL_HOURS: i64 = 1;
+/// Wait before downloading a notice letter again after a failure that may pass, doubled for every failure in a row up
+/// to the maximum
+const DOCUMENT_RETRY_HOURS: i64 = 1;
+const DOCUMENT_MAX_RETRY_HOURS: i64 = 7 * 24;
+
+/// Outcome of retrieving every set of options of one source, sent from the retrieving futures to the writer once its
+/// pages were handed over
+struct Retrieved {
+	state: State,
+	run_id: Option<i32>,
+	started_at: NaiveDateTime,
+	result: Result<Retrieval>,
+}
+
+/// Everything the retrieving futures send to the writer. The writer acknowledges pages and completed urls once they are
+/// stored, so retrieval never runs ahead of what is persisted and no more than a page per source is held in memory.
+enum Message {
+	Page { state: State, base_url: String, breaches: Vec<Breach>, next: PageCursor, stored: oneshot::Sender<Result<StoredPage>> },
+	Completed { state: State, base_url: String, stored: oneshot::Sender<Result<()>> },
+	Fetched(FetchedPage),
+	Finished(Retrieved),
+}
+
+/// What `retrieve_all` runs for, every mode hands each page to the writer as soon as it is parsed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+	Scrape,
+	Backfill,
+	/// Does not archive the responses
+	DryRun,
+}
+
+/// Hands what is known about every archived response to the writer
+struct ChannelRecorder {
+	tx: mpsc::Sender<Message>,
+}
+
+#[async_trait]
+impl PageRecorder for ChannelRecorder {
+	async fn record(&self, page: FetchedPage) {
+		_ = self.tx.send(Message::Fetched(page)).await;
+	}
+}
+
+/// Hands every page to the writer and waits until it is stored
+struct ChannelSink {
+	tx: mpsc::Sender<Message>,
+}
+
+#[async_trait]
+impl PageSink for ChannelSink {
+	async fn page(&self, options: &RetrieverOptions, breaches: Vec<Breach>, next: PageCursor) -> Result<StoredPage> {
+		let (stored, rx) = oneshot::channel();
+		_ = self.tx.send(Message::Page { state: options.state, base_url: options.base_url.clone(), breaches, next, stored }).await;
+
+		rx.await.unwrap_or(Ok(StoredPage::default()))
+	}
+}
+
+impl ChannelSink {
+	async fn complete(&self, options: &RetrieverOptions) -> Result<()> {
+		let (stored, rx) = oneshot::channel();
+		_ = self.tx.send(Message::Completed { state: options.state, base_url: options.base_url.clone(), stored }).await;
+
+		rx.await.unwrap_or(Ok(()))
+	}
+}
+
+/// A source and its options, with the scrape_run recording it unless this is a dry run
+struct Job<'a> {
+	source: &'a dyn Source,
+	run_id: Option<i32>,
+	options: Vec<RetrieverOptions>,
+}
+
+/// Counts collected over one source run, stored in its scrape_run record
+#[derive(Debug, Default)]
+struct RunStats {
+	pages_fetched: usize,
+	rows_parsed: usize,
+	rows_inserted: usize,
+	classifications_added: usize,
+	/// The source responded that nothing changed since the last run
+	unchanged: bool,
+	/// Latest reported date parsed, the last retrieved date once the run succeeds
+	newest_parsed: Option<NaiveDateTime>,
+}
+
+pub struct Processor {
+	sources: Vec<Box<dyn Source>>,
+	max_concurrency: usize,
+	hosts: Arc<Hosts>,
+	archive: Option<Archive>,
+	replay: Option<Arc<Snapshots>>,
+	warc: Option<Arc<WarcWriter>>,
+	/// Client of every source, built from the http settings and its TLS settings
+	clients: HashMap<State, Client>,
+	/// Client of urls no source is configured for, built from the http settings alone
+	client: Client,
+	http: HttpConfig,
+	/// Identifies the source locks taken by this processor
+	holder: String,
+}
+
+impl Processor {
+	/// Retrieves every source concurrently, up to `max_concurrency` at a time. Every page is stored as soon as it is
+	/// parsed by a single writer, so SQLite only ever sees one write at a time and the breaches of pages stored before a
+	/// failure are kept. A failing source does not stop the others, all failures are returned together once every source
+	/// is done. Every source run is recorded in scrape_run. Sources that are locked by another run are skipped.
+	pub async fn process(&self, conn: &mut SqliteConnection) -> Result<()> {
+		let mut errors = vec!();
+		let mut jobs = vec!();
+
+		for source in self.sources.iter() {
+			let state = source.state();
+			match self.lock(conn, state) {
+				Ok(true) => {},
+				Ok(false) => continue,
+				Err(err) => {
+					errors.push(err);
+					continue;
+				},
+			}
+
+			match self.scrape_job(conn, source.as_ref()) {
+				Ok(job) => jobs.push(job),
+				Err(err) => {
+					errors.push(err);
+					errors.extend(self.unlock(conn, state).err());
+				},
+			}
+		}
+
+		let mut stats: HashMap<State, RunStats> = HashMap::new();
+		self.retrieve_all(jobs, Mode::Scrape, |message| {
+			let retrieved = match message {
+				Message::Finished(retrieved) => retrieved,
+				Message::Page { state, breaches, stored, .. } => {
+					if let Err(err) = self.renew(conn) {
+						eprintln!("{}", err);
+						errors.push(err);
+					}
+					let stats = stats.entry(state).or_default();
+					_ = stored.send(Processor::store(conn, &breaches, stats).map_err(Error::storage(state)));
+					return;
+				},
+				Message::Fetched(page) => {
+					if let Err(err) = Processor::record_fetched(conn, page) {
+						eprintln!("{}", err);
+						errors.push(err);
+					}
+					return;
+				},
+				Message::Completed { .. } => return,
+			};
+			let state = retrieved.state;
+			let mut stats = stats.remove(&state).unwrap_or_default();
+
+			let result = retrieved.result.and_then(|retrieval| {
+				stats.pages_fetched = retrieval.pages_fetched;
+				stats.unchanged = retrieval.unchanged;
+
+				if retrieval.unchanged {
+					println!("Nothing changed in {:?} since the last run", state);
+					return Ok(());
+				}
+
+				Processor::complete(conn, state, &retrieval, &stats).map_err(Error::storage(state))
+			});
+
+			let finished = match retrieved.run_id {
+				Some(run_id) => Processor::finish_run(conn, state, run_id, retrieved.started_at, &stats, result.as_ref().err()),
+				None => Ok(()),
+			};
+			let unlocked = self.unlock(conn, state);
+
+			for err in [result.err(), finished.err(), unlocked.err()].into_iter().flatten() {
+				eprintln!("{}", err);
+				errors.push(err);
+			}
+		}).await;
+
+		if errors.is_empty() {
+			Ok(())
+		}
+		else {
+			Err(Error::Sources(errors))
+		}
+	}
+
+	/// Retrieves and parses every source like `process`, but only compares the results with what is stored. Nothing is
+	/// written, not even the scrape_run records. Failing sources are reported instead of returned.
+	pub async fn dry_run(&self, conn: &mut SqliteConnection) -> Result<DryRunReport> {
+		let mut report = DryRunReport::default();
+		let mut jobs = vec!();
+
+		for source in self.sources.iter() {
+			match self.options(conn, source.as_ref()) {
+				Ok(options) => jobs.push(Job { source: source.as_ref(), run_id: None, options }),
+				Err(err) => report.states.push(StateDiff::failed(source.state(), err.to_string())),
+			}
+		}
+
+		let mut storage_error = None;
+		let mut parsed: HashMap<State, Vec<Breach>> = HashMap::new();
+		self.retrieve_all(jobs, Mode::DryRun, |message| {
+			let retrieved = match message {
+				Message::Finished(retrieved) => retrieved,
+				Message::Page { state, mut breaches, stored, .. } => {
+					let known: QueryResult<Vec<bool>> = breaches.iter().map(|breach| find_breach(conn, &new_breach_data(breach).0).map(|found| found.is_some())).collect();
+					_ = stored.send(known.map(|known| StoredPage { known }).map_err(Error::storage(state)));
+					parsed.entry(state).or_default().append(&mut breaches);
+					return;
+				},
+				_ => return,
+			};
+			let state = retrieved.state;
+			let breaches = parsed.remove(&state).unwrap_or_default();
+			let diff = match retrieved.result {
+				Ok(_) => diff_breaches(conn, state, breaches).map_err(Error::storage(state)),
+				Err(err) => Ok(StateDiff::failed(state, err.to_string())),
+			};
+
+			match diff {
+				Ok(diff) => report.states.push(diff),
+				Err(err) => storage_error = Some(err),
+			}
+		}).await;
+
+		match storage_error {
+			Some(err) => Err(err),
+			None => Ok(report),
+		}
+	}
+
+	/// Retrieves every source back to its initial date regardless of the last retrieved date. Every page is stored as
+	/// soon as it is parsed, together with a checkpoint of where the backfill continues, so a failed backfill resumes from
+	/// its last stored page. Urls that were backfilled completely are skipped until `restart` discards the checkpoints.
+	pub async fn backfill(&self, conn: &mut SqliteConnection, restart: bool) -> Result<()> {
+		let mut errors = vec!();
+		let mut jobs = vec!();
+		let mut checkpoints: HashMap<(State, String), BackfillCheckpoint> = HashMap::new();
+
+		for source in self.sources.iter() {
+			let state = source.state();
+			match self.lock(conn, state) {
+				Ok(true) => {},
+				Ok(false) => continue,
+				Err(err) => {
+					errors.push(err);
+					continue;
+				},
+			}
+
+			match self.backfill_job(conn, source.as_ref(), restart, &mut checkpoints) {
+				Ok(Some(job)) => jobs.push(job),
+				Ok(None) => errors.extend(self.unlock(conn, state).err()),
+				Err(err) => {
+					errors.push(err);
+					errors.extend(self.unlock(conn, state).err());
+				},
+			}
+		}
+
+		let mut stats: HashMap<State, RunStats> = HashMap::new();
+		self.retrieve_all(jobs, Mode::Backfill, |message| match message {
+			Message::Page { state, base_url, breaches, next, stored } => {
+				if let Err(err) = self.renew(conn) {
+					eprintln!("{}", err);
+					errors.push(err);
+				}
+				let stats = stats.entry(state).or_default();
+				let result = match checkpoints.get_mut(&(state, base_url)) {
+					Some(checkpoint) => Processor::store_page(conn, checkpoint, &breaches, next, stats).map_err(Error::storage(state)),
+					None => Ok(StoredPage::default()),
+				};
+
+				_ = stored.send(result);
+			},
+			Message::Completed { state, base_url, stored } => {
+				let result = match checkpoints.get_mut(&(state, base_url)) {
+					Some(checkpoint) => {
+						checkpoint.completed = true;
+						update_backfill_checkpoint(conn, checkpoint.id, BackfillProgress::from(&*checkpoint)).map_err(Error::storage(state))
+					},
+					None => Ok(()),
+				};
+
+				_ = stored.send(result);
+			},
+			Message::Fetched(page) => {
+				if let Err(err) = Processor::record_fetched(conn, page) {
+					eprintln!("{}", err);
+					errors.push(err);
+				}
+			},
+			Message::Finished(retrieved) => {
+				let state = retrieved.state;
+				let mut stats = stats.remove(&state).unwrap_or_default();
+				let result = retrieved.result.and_then(|retrieval| {
+					stats.pages_fetched = retrieval.pages_fetched;
+					Processor::backfilled(conn, state, &stats).map_err(Error::storage(state))
+				});
+
+				let finished = match retrieved.run_id {
+					Some(run_id) => Processor::finish_run(conn, state, run_id, retrieved.started_at, &stats, result.as_ref().err()),
+					None => Ok(()),
+				};
+
+				match result {
+					Ok(_) => println!("Backfilled {:?}, inserted {} breaches from {} page(s)", state, stats.rows_inserted, stats.pages_fetched),
+					Err(err) => {
+						eprintln!("{}", err);
+						errors.push(err);
+					},
+				}
+
+				for err in [finished.err(), self.unlock(conn, state).err()].into_iter().flatten() {
+					eprintln!("{}", err);
+					errors.push(err);
+				}
+			},
+		}).await;
+
+		if errors.is_empty() {
+			Ok(())
+		}
+		else {
+			Err(Error::Sources(errors))
+		}
+	}
+
+	/// Downloads the notice letters due for a download into the archive, up to `max_concurrency` at a time, and records
+	/// every attempt in notice_document
+	pub async fn documents(&self, conn: &mut SqliteConnection) -> Result<()> {
+		let Some(archive) = &self.archive else {
+			return Err(Error::config("Notice documents are stored in the archive, which is turned off"));
+		};
+
+		let now = Utc::now().naive_utc();
+		let mut downloads = vec!();
+		for source in self.sources.iter() {
+			let state = source.state();
+			for (breach_data_id, link, attempts) in get_missing_notice_documents(conn, state.into(), now).map_err(Error::storage(state))? {
+				downloads.push((breach_data_id, attempts, source.document_options(&link)));
+			}
+		}
+
+		let mut fetcher = Fetcher::new(self.clients.clone(), self.client.clone(), self.http.clone(), self.hosts.clone());
+		if let Some(warc) = &self.warc {
+			fetcher = fetcher.with_warc(warc.clone());
+		}
+
+		let fetcher = &fetcher;
+		let mut responses = stream::iter(downloads).map(|(breach_data_id, attempts, options)| async move {
+			let response = download(fetcher, &options.base_url, &options).await;
+
+			(breach_data_id, attempts, options, response)
+		}).buffer_unordered(self.max_concurrency);
+
+		let mut errors = vec!();
+		let mut downloaded = 0;
+		while let Some((breach_data_id, attempts, options, response)) = responses.next().await {
+			let stored = match response {
+				Ok(response) => Processor::store_document(conn, archive, options.state, breach_data_id, &response),
+				Err(err) => {
+					if let Err(record_err) = Processor::record_document_failure(conn, breach_data_id, attempts + 1, &options, &err) {
+						errors.push(record_err);
+					}
+
+					Err(err.into_error(options.state, options.base_url.clone(), 0))
+				},
+			};
+
+			match stored {
+				Ok(()) => downloaded += 1,
+				Err(err) => {
+					eprintln!("{}", err);
+					errors.push(err);
+				},
+			}
+		}
+
+		println!("Downloaded {} notice document(s)", downloaded);
+
+		if errors.is_empty() {
+			Ok(())
+		}
+		else {
+			Err(Error::Sources(errors))
+		}
+	}
+
+	/// Records the start of a scrape of a locked source and builds its job. A run that cannot get its options is finished
+	/// as failed, the caller unlocks the source whenever this fails.
+	fn scrape_job<'a>(&self, conn: &mut SqliteConnection, source: &'a dyn Source) -> Result<Job<'a>> {
+		let state = source.state();
+		let started_at = Utc::now().naive_utc();
+		let run_id = create_scrape_run(conn, NewScrapeRun { loc: state.into(), run_status: RunStatus::Running, started_at })
+			.map_err(Error::storage(state))?;
+
+		match self.options(conn, source).and_then(|options| self.with_validators(conn, state, options)) {
+			Ok(options) => Ok(Job { source, run_id: Some(run_id), options }),
+			Err(err) => {
+				if let Err(finish_err) = Processor::finish_run(conn, state, run_id, started_at, &RunStats::default(), Some(&err)) {
+					eprintln!("{}", finish_err);
+				}
+
+				Err(err)
+			},
+		}
+	}
+
+	/// Builds the backfill job of a locked source from its checkpoints, none when every url of it was backfilled
+	/// completely. The run is only recorded once everything else succeeded, the caller unlocks the source whenever this
+	/// fails or returns none.
+	fn backfill_job<'a>(&self, conn: &mut SqliteConnection, source: &'a dyn Source, restart: bool, checkpoints: &mut HashMap<(State, String), BackfillCheckpoint>) -> Result<Option<Job<'a>>> {
+		let state = source.state();
+		if restart {
+			delete_backfill_checkpoints(conn, state.into()).map_err(Error::storage(state))?;
+		}
+
+		let existing = get_backfill_checkpoints(conn, state.into()).map_err(Error::storage(state))?;
+		let mut options = vec!();
+		for mut opt in source.backfill_options() {
+			let checkpoint = match existing.iter().find(|checkpoint| checkpoint.base_url == opt.base_url) {
+				Some(checkpoint) => checkpoint.clone(),
+				None => create_backfill_checkpoint(conn, NewBackfillCheckpoint { loc: state.into(), base_url: opt.base_url.clone(), updated_at: Utc::now().naive_utc() })
+					.map_err(Error::storage(state))?,
+			};
+
+			if checkpoint.completed {
+				println!("Backfill of {} in {:?} already completed, use --restart to backfill it again", opt.base_url, state);
+				continue;
+			}
+
+			if checkpoint.pages_fetched > 0 {
+				println!("Resuming backfill of {} in {:?} after {} page(s)", opt.base_url, state, checkpoint.pages_fetched);
+				opt.start = PageCursor { page: checkpoint.page, next_url_part: checkpoint.next_url_part.clone(), group: checkpoint.group_name.clone() };
+			}
+
+			checkpoints.insert((state, opt.base_url.clone()), checkpoint);
+			options.push(opt);
+		}
+
+		if options.is_empty() {
+			return Ok(None);
+		}
+
+		let run_id = create_scrape_run(conn, NewScrapeRun { loc: state.into(), run_status: RunStatus::Running, started_at: Utc::now().naive_utc() })
+			.map_err(Error::storage(state))?;
+
+		Ok(Some(Job { source, run_id: Some(run_id), options }))
+	}
+
+	/// Retrieves the jobs concurrently and hands every message to `write`, which runs on the calling task only
+	async fn retrieve_all(&self, jobs: Vec<Job<'_>>, mode: Mode, mut write: impl FnMut(Message)) {
+		let (tx, mut rx) = mpsc::channel::<Message>(self.max_concurrency);
+
+		let mut fetcher = Fetcher::new(self.clients.clone(), self.client.clone(), self.http.clone(), self.hosts.clone());
+		if mode != Mode::DryRun && self.replay.is_none() {
+			if let Some(archive) = &self.archive {
+				fetcher = fetcher.with_archive(archive.clone(), Box::new(ChannelRecorder { tx: tx.clone() }));
+			}
+			if let Some(warc) = &self.warc {
+				fetcher = fetcher.with_warc(warc.clone());
+			}
+		}
+
+		let retrieve_all = async move {
+			stream::iter(jobs).for_each_concurrent(self.max_concurrency, |job| {
+				let tx = tx.clone();
+				let fetcher = &fetcher;
+				async move {
+					let started_at = Utc::now().naive_utc();
+					let sink = ChannelSink { tx: tx.clone() };
+
+					let mut options = job.options;
+					options.iter_mut().for_each(|opt| opt.run_id = job.run_id);
+
+					let result = Processor::retrieve(fetcher, self.replay.as_ref(), job.source, &options, &sink).await;
+					_ = tx.send(Message::Finished(Retrieved { state: job.source.state(), run_id: job.run_id, started_at, result })).await;
+				}
+			}).await;
+		};
+
+		let write_all = async {
+			while let Some(message) = rx.recv().await {
+				write(message);
+			}
+		};
+
+		tokio::join!(retrieve_all, write_all);
+	}
+
+	/// Options of a source run. A replay collects back to the initial date, the snapshots decide how far it goes.
+	fn options(&self, conn: &mut SqliteConnection, source: &dyn Source) -> Result<Vec<RetrieverOptions>> {
+		match self.replay {
+			Some(_) => Ok(source.backfill_options()),
+			None => source.options(conn),
+		}
+	}
+
+	/// Sends the validators of the last stored response of every url along, so urls that did not change are skipped
+	fn with_validators(&self, conn: &mut SqliteConnection, state: State, mut options: Vec<RetrieverOptions>) -> Result<Vec<RetrieverOptions>> {
+		if self.replay.is_some() {
+			return Ok(options);
+		}
+
+		let cache = get_http_cache(conn, state.into()).map_err(Error::storage(state))?;
+		for opt in options.iter_mut() {
+			opt.validators = cache.iter()
+				.find(|cache| cache.url == opt.base_url)
+				.map(|cache| Validators { etag: cache.etag.clone(), last_modified: cache.last_modified.clone() });
+		}
+
+		Ok(options)
+	}
+
+	/// Retrieves the options of a source one after the other, a source only succeeds when all of its options do. A source
+	/// is unchanged when every one of its options is.
+	async fn retrieve(fetcher: &Fetcher, replay: Option<&Arc<Snapshots>>, source: &dyn Source, options: &[RetrieverOptions], sink: &ChannelSink) -> Result<Retrieval> {
+		let mut retrieval = Retrieval { unchanged: !options.is_empty(), ..Default::default() };
+
+		for opt in options {
+			let retriever: Box<dyn Retriever> = match replay {
+				Some(snapshots) => Box::new(Replay::new(source.configured_retriever(), snapshots.clone())),
+				None => source.configured_retriever(),
+			};
+
+			let mut ret = retriever.retrieve(fetcher, source.parser(), opt, sink).await?;
+
+			retrieval.pages_fetched += ret.pages_fetched;
+			retrieval.unchanged &= ret.unchanged;
+			retrieval.validators.append(&mut ret.validators);
+
+			sink.complete(opt).await?;
+		}
+
+		Ok(retrieval)
+	}
+
+	/// Stores the breaches of one page of a source run in one transaction. The last retrieved date is left alone until the
+	/// whole run succeeded, so a run failing part way through is retrieved back as far again by the next one.
+	fn store(conn: &mut SqliteConnection, breaches: &[Breach], stats: &mut RunStats) -> QueryResult<StoredPage> {
+		let (inserted_breaches_count, classifications_count, known) = conn.transaction(|conn| Processor::insert_breaches(conn, breaches))?;
+
+		stats.rows_parsed += breaches.len();
+		stats.rows_inserted += inserted_breaches_count;
+		stats.classifications_added += classifications_count;
+		stats.newest_parsed = breaches.iter().map(|breach| breach.date_reported).chain(stats.newest_parsed).max();
+
+		Ok(StoredPage { known })
+	}
+
+	/// Advances the last retrieved date of a succeeded source run past its stored breaches. The validators of the fetched
+	/// urls are stored in the same transaction, so a url is only skipped as unchanged once its breaches are in.
+	fn complete(conn: &mut SqliteConnection, state: State, retrieval: &Retrieval, stats: &RunStats) -> QueryResult<()> {
+		let last_retrieved = stats.newest_parsed.filter(|_| stats.rows_inserted > 0);
+
+		conn.transaction(|conn| {
+			if let Some(retrieved_date) = last_retrieved {
+				insert_last_retrieved(conn, NewLastRetrieved { loc: state.into(), retrieved_date })?;
+			}
+
+			for (url, validators) in retrieval.validators.iter() {
+				save_http_cache(conn, HttpCache {
+					loc: state.into(),
+					url: url.clone(),
+					etag: validators.etag.clone(),
+					last_modified: validators.last_modified.clone(),
+					updated_at: Utc::now().naive_utc(),
+				})?;
+			}
+
+			QueryResult::Ok(())
+		})?;
+
+		match last_retrieved {
+			Some(date) => println!("Inserted total of {} breaches in {:?}, last retrieved {}", stats.rows_inserted, state, date),
+			None => println!("No new breaches to insert in {:?}", state),
+		}
+
+		Ok(())
+	}
+
+	/// Advances the last retrieved date of a succeeded backfill past the breaches it stored
+	fn backfilled(conn: &mut SqliteConnection, state: State, stats: &RunStats) -> QueryResult<()> {
+		match stats.newest_parsed.filter(|_| stats.rows_inserted > 0) {
+			Some(retrieved_date) => insert_last_retrieved(conn, NewLastRetrieved { loc: state.into(), retrieved_date }),
+			None => Ok(()),
+		}
+	}
+
+	/// Stores the breaches of one backfill page and moves its checkpoint past that page in one transaction
+	fn store_page(conn: &mut SqliteConnection, checkpoint: &mut BackfillCheckpoint, breaches: &[Breach], next: PageCursor, stats: &mut RunStats) -> QueryResult<StoredPage> {
+		let state: State = checkpoint.loc.into();
+		let mut progress = BackfillProgress::from(&*checkpoint);
+		progress.page = next.page;
+		progress.next_url_part = next.next_url_part.clone();
+		progress.group_name = next.group.clone();
+		progress.pages_fetched += 1;
+
+		let (inserted_breaches_count, classifications_count, known) = conn.transaction(|conn| {
+			let (i, c, known) = Processor::insert_breaches(conn, breaches)?;
+			progress.rows_inserted += i as i32;
+
+			update_backfill_checkpoint(conn, checkpoint.id, progress)?;
+
+			QueryResult::Ok((i, c, known))
+		})?;
+
+		checkpoint.page = next.page;
+		checkpoint.next_url_part = next.next_url_part;
+		checkpoint.group_name = next.group;
+		checkpoint.pages_fetched += 1;
+		checkpoint.rows_inserted += inserted_breaches_count as i32;
+		stats.rows_parsed += breaches.len();
+		stats.rows_inserted += inserted_breaches_count;
+		stats.classifications_added += classifications_count;
+		stats.newest_parsed = breaches.iter().map(|breach| breach.date_reported).chain(stats.newest_parsed).max();
+
+		println!("Stored backfill page {} of {:?}, {} new breaches", checkpoint.pages_fetched, state, inserted_breaches_count);
+
+		Ok(StoredPage { known })
+	}
+
+	/// Inserts breaches and their classifications, returning how many of each were new and which breaches were stored
+	/// already
+	fn insert_breaches(conn: &mut SqliteConnection, breaches: &[Breach]) -> QueryResult<(usize, usize, Vec<bool>)> {
+		let mut inserted_breaches_count = 0;
+		let mut classifications_count = 0;
+		let mut known = vec!();
+		for breach in breaches {
+			let (i, c) = create_breach_data(conn, breach)?;
+			known.push(i == 0);
+
+			inserted_breaches_count += i;
+			classifications_count += c;
+			if i == 0 && c > 0 {
+				println!("Created {} new classification(s) for {:?}", c, breach);
+			}
+		}
+
+		Ok((inserted_breaches_count, classifications_count, known))
+	}
+
+	/// Takes the lock of a source so no other run of it overlaps this one, a source whose lock is held is skipped
+	fn lock(&self, conn: &mut SqliteConnection, state: State) -> Result<bool> {
+		let now = Utc::now().naive_utc();
+		let lock = NewSourceLock { loc: state.into(), holder: self.holder.clone(), acquired_at: now, expires_at: now + Duration::hours(LOCK_TTL_HOURS) };
+
+		let locked = try_lock_source(conn, lock).map_err(Error::storage(state))?;
+		if !locked {
+			eprintln!("Skipping {:?}, another run of it is in progress", state);
+		}
+
+		Ok(locked)
+	}
+
+	/// Keeps the locks of the sources still running from expiring, including those waiting for their turn
+	fn renew(&self, conn: &mut SqliteConnection) -> Result<()> {
+		renew_source_locks(conn, &self.holder, Utc::now().naive_utc() + Duration::hours(LOCK_TTL_HOURS))?;
+
+		Ok(())
+	}
+
+	fn unlock(&self, conn: &mut SqliteConnection, state: State) -> Result<()> {
+		unlock_source(conn, state.into(), &self.holder).map_err(Error::storage(state))
+	}
+
+	fn store_document(conn: &mut SqliteConnection, archive: &Archive, state: State, breach_data_id: i32, response: &RawResponse) -> Result<()> {
+		let content_hash = archive.store(&response.body)
+			.map_err(|source| Error::Io { context: format!("could not archive {}", response.url), source })?;
+		let mime_type = response.headers.get(CONTENT_TYPE)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| value.split(';').next())
+			.map(|value| value.trim().to_ascii_lowercase())
+			.filter(|value| !value.is_empty())
+			.unwrap_or_else(|| "application/octet-stream".to_string());
+
+		save_notice_document(conn, NewNoticeDocument {
+			breach_data_id,
+			link: response.url.clone(),
+			content_hash: Some(content_hash),
+			mime_type: Some(mime_type),
+			size: Some(response.body.len() as i32),
+			fetched_at: Some(response.fetched_at),
+			status: Some(response.status.as_u16() as i32),
+			error: None,
+			attempts: 0,
+			attempted_at: response.fetched_at,
+			retry_at: None,
+		}).map_err(Error::storage(state))
+	}
+
+	/// Records a failed download of a notice letter. A failure that may pass, like a timeout or a 503, is attempted again
+	/// after a backoff. Any other one, like a 404, would fail the same way again, so the letter is only attempted again
+	/// once its breach links elsewhere and the link no longer matches. Failing to write the WARC file says nothing about
+	/// the letter and is not recorded.
+	fn record_document_failure(conn: &mut SqliteConnection, breach_data_id: i32, attempts: i32, options: &RetrieverOptions, err: &FetchError) -> Result<()> {
+		if matches!(err, FetchError::Archive(_)) {
+			return Ok(());
+		}
+
+		let attempted_at = Utc::now().naive_utc();
+		let retry_at = err.is_retryable().then(|| {
+			let hours = DOCUMENT_RETRY_HOURS.saturating_mul(1 << (attempts - 1).clamp(0, 16)).min(DOCUMENT_MAX_RETRY_HOURS);
+			attempted_at + Duration::hours(hours)
+		});
+		let status = match err {
+			FetchError::Status { status, .. } => Some(status.as_u16() as i32),
+			_ => None,
+		};
+
+		save_notice_document(conn, NewNoticeDocument {
+			breach_data_id,
+			link: options.base_url.clone(),
+			content_hash: None,
+			mime_type: None,
+			size: None,
+			fetched_at: None,
+			status,
+			error: Some(err.to_string()),
+			attempts,
+			attempted_at,
+			retry_at,
+		}).map_err(Error::storage(options.state))
+	}
+
+	fn record_fetched(conn: &mut SqliteConnection, page: FetchedPage) -> Result<()> {
+		let state = page.state;
+
+		create_fetched_page(conn, NewFetchedPage {
+			run_id: page.run_id,
+			loc: state.into(),
+			url: page.url,
+			method: page.method,
+			request_headers: headers_json(&page.request_headers),
+			status: page.status.as_u16() as i32,
+			response_headers: headers_json(&page.response_headers),
+			fetched_at: page.fetched_at,
+			content_hash: page.content_hash,
+			size: page.size as i32,
+			request_body_hash: Some(page.request_body_hash),
+		}).map_err(Error::storage(state))
+	}
+
+	fn finish_run(conn: &mut SqliteConnection, state: State, run_id: i32, started_at: NaiveDateTime, stats: &RunStats, error: Option<&Error>) -> Result<()> {
+		let watermark = get_last_retrieved(conn, state.into()).map_err(Error::storage(state))?;
+
+		let run = FinishedScrapeRun {
+			run_status: match (error, stats.unchanged) {
+				(Some(_), _) => RunStatus::Failed,
+				(None, true) => RunStatus::Unchanged,
+				(None, false) => RunStatus::Succeeded,
+			},
+			started_at,
+			finished_at: Utc::now().naive_utc(),
+			pages_fetched: stats.pages_fetched as i32,
+			rows_parsed: stats.rows_parsed as i32,
+			rows_inserted: stats.rows_inserted as i32,
+			classifications_added: stats.classifications_added as i32,
+			error_count: error.map(|err| match err {
+				Error::Sources(errors) => errors.len() as i32,
+				_ => 1,
+			}).unwrap_or(0),
+			errors: error.map(|err| err.to_string()),
+			watermark: watermark.map(|lr| lr.retrieved_date),
+			error_kind: error.map(|err| err.kind().name().to_string()),
+		};
+
+		finish_scrape_run(conn, run_id, run).map_err(Error::storage(state))
+	}
+}
+
+pub struct ProcessorBuilder {
+	sources: Vec<Box<dyn Source>>,
+	max_concurrency: usize,
+	politeness: PolitenessPolicy,
+	hosts: Option<Arc<Hosts>>,
+	http: HttpConfig,
+	archive: Option<Archive>,
+	replay: Option<Snapshots>,
+	warc: Option<PathBuf>,
+}
+
+impl Default for ProcessorBuilder {
+	fn default() -> Self {
+		Self {
+			sources: vec!(),
+			max_concurrency: DEFAULT_MAX_CONCURRENCY,
+			politeness: PolitenessPolicy::default(),
+			hosts: None,
+			http: HttpConfig::default(),
+			archive: None,
+			replay: None,
+			warc: None,
+		}
+	}
+}
+
+impl ProcessorBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn source(mut self, source: Box<dyn Source>) -> ProcessorBuilder {
+		self.sources.push(source);
+		self
+	}
+
+	pub fn sources(mut self, sources: Vec<Box<dyn Source>>) -> ProcessorBuilder {
+		for source in sources {
+			self.sources.push(source);
+		}
+
+		self
+	}
+
+	pub fn max_concurrency(mut self, max_concurrency: usize) -> ProcessorBuilder {
+		self.max_concurrency = max_concurrency;
+		self
+	}
+
+	pub fn politeness(mut self, politeness: PolitenessPolicy) -> ProcessorBuilder {
+		self.politeness = politeness;
+		self
+	}
+
+	/// Waits on the token buckets and robots.txt of hosts shared with other processors, instead of a politeness of its own
+	pub fn hosts(mut self, hosts: Arc<Hosts>) -> ProcessorBuilder {
+		self.hosts = Some(hosts);
+		self
+	}
+
+	pub fn http(mut self, http: HttpConfig) -> ProcessorBuilder {
+		self.http = http;
+		self
+	}
+
+	/// Archives every response body fetched by `process` and `backfill`
+	pub fn archive(mut self, archive: Archive) -> ProcessorBuilder {
+		self.archive = Some(archive);
+		self
+	}
+
+	/// Writes every request and response of `process` and `backfill` to the WARC file, appending when it exists
+	pub fn warc(mut self, path: PathBuf) -> ProcessorBuilder {
+		self.warc = Some(path);
+		self
+	}
+
+	/// Serves every page from the snapshots instead of fetching it, nothing is archived while replaying
+	pub fn replay(mut self, snapshots: Snapshots) -> ProcessorBuilder {
+		self.replay = Some(snapshots);
+		self
+	}
+
+	pub fn build(self) -> Result<Processor> {
+		if self.sources.is_empty() {
+			return Err(Error::config("Cannot create processor without any sources"))
+		}
+
+		if self.max_concurrency == 0 {
+			return Err(Error::config("Cannot create processor with a max concurrency of 0"))
+		}
+
+		let factory = ClientFactory::new(self.http);
+		let mut clients = HashMap::new();
+		for source in self.sources.iter() {
+			clients.insert(source.state(), factory.client(&source.config().tls)?);
+		}
+
+		Ok(Processor {
+			clients,
+			client: factory.client(&TlsConfig::default())?,
+			http: factory.config().clone(),
+			sources: self.sources,
+			max_concurrency: self.max_concurrency,
+			hosts: self.hosts.unwrap_or_else(|| Arc::new(Hosts::new(self.politeness))),
+			archive: self.archive,
+			replay: self.replay.map(Arc::new),
+			warc: self.warc.map(|path| Arc::new(WarcWriter::new(path))),
+			holder: format!("pid {} at {}", process::id(), Utc::now()),
+		})
+	}
+}
@@ -0,0 +1,103 @@
+use std::{collections::BTreeMap, fs::{self, File}, io::{self, Read, Write}, path::PathBuf, process};
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use reqwest::{StatusCode, header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION}};
+use sha2::{Digest, Sha256};
+
+use crate::dto::State;
+
+/// Raw response bodies stored gzip compressed under their sha256, so a body fetched twice is only stored once
+#[derive(Debug, Clone)]
+pub struct Archive {
+	dir: PathBuf,
+}
+
+/// Everything known about one response besides its body, which is in the archive under `content_hash`
+#[derive(Debug)]
+pub struct FetchedPage {
+	pub state: State,
+	pub run_id: Option<i32>,
+	pub url: String,
+	pub method: String,
+	pub request_headers: HeaderMap,
+	pub status: StatusCode,
+	pub response_headers: HeaderMap,
+	pub fetched_at: NaiveDateTime,
+	pub content_hash: String,
+	pub size: usize,
+	/// sha256 of the request body, which tells apart pages posted to the same url
+	pub request_body_hash: String,
+}
+
+/// Receives a `FetchedPage` for every archived body
+#[async_trait]
+pub trait PageRecorder: Send + Sync {
+	async fn record(&self, page: FetchedPage);
+}
+
+impl Archive {
+	pub fn new(dir: PathBuf) -> Archive {
+		Archive { dir }
+	}
+
+	/// Stores the body unless a body with the same content is already stored, returns its hash
+	pub fn store(&self, body: &[u8]) -> io::Result<String> {
+		let hash = content_hash(body);
+		let path = self.path(&hash);
+		if path.exists() {
+			return Ok(hash);
+		}
+
+		fs::create_dir_all(path.parent().unwrap())?;
+
+		// Written next to its final name and renamed, so a crash never leaves a partial body under a valid hash
+		let temp_path = path.with_extension(format!("{}.tmp", process::id()));
+		let mut encoder = GzEncoder::new(File::create(&temp_path)?, Compression::default());
+		encoder.write_all(body)?;
+		encoder.finish()?.sync_all()?;
+		fs::rename(&temp_path, &path)?;
+
+		Ok(hash)
+	}
+
+	pub fn load(&self, hash: &str) -> io::Result<Vec<u8>> {
+		let mut body = vec!();
+		GzDecoder::new(File::open(self.path(hash))?).read_to_end(&mut body)?;
+
+		Ok(body)
+	}
+
+	fn path(&self, hash: &str) -> PathBuf {
+		self.dir.join(&hash[..2]).join(format!("{}.gz", hash))
+	}
+}
+
+/// sha256 of the bytes in hex, the name bodies are archived under
+pub fn content_hash(bytes: &[u8]) -> String {
+	format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Value of a header as it is recorded, credentials are replaced so they never end up in the database or an export
+pub fn header_value(name: &HeaderName, value: &HeaderValue) -> String {
+	match *name {
+		AUTHORIZATION | COOKIE | PROXY_AUTHORIZATION => "<redacted>".to_string(),
+		_ => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+	}
+}
+
+/// Headers as a JSON object with repeated headers joined by commas, credentials redacted
+pub fn headers_json(headers: &HeaderMap) -> String {
+	let mut json: BTreeMap<&str, String> = BTreeMap::new();
+
+	for (name, value) in headers.iter() {
+		let value = header_value(name, value);
+
+		json.entry(name.as_str())
+			.and_modify(|values| { values.push_str(", "); values.push_str(&value); })
+			.or_insert(value);
+	}
+
+	serde_json::to_string(&json).unwrap_or_default()
+}
@@ -0,0 +1,58 @@
+use serde_json::Value;
+
+/// Replaced with the page number, or the url part the parser returned for the next page, wherever it appears in a body
+pub const PAGE_PLACEHOLDER: &str = "{page}";
+
+/// Body sent with every POST of a source, rendered again for every page
+#[derive(Debug, Clone)]
+pub struct RequestBody {
+	pub content_type: String,
+	pub content: BodyContent,
+}
+
+#[derive(Debug, Clone)]
+pub enum BodyContent {
+	/// Form fields, sent url encoded
+	Form(Vec<(String, String)>),
+	Json(Value),
+	Raw(String),
+}
+
+impl BodyContent {
+	pub fn default_content_type(&self) -> &'static str {
+		match self {
+			BodyContent::Form(_) => "application/x-www-form-urlencoded",
+			BodyContent::Json(_) => "application/json",
+			BodyContent::Raw(_) => "text/plain; charset=utf-8",
+		}
+	}
+}
+
+impl RequestBody {
+	/// The body of the given page
+	pub fn render(&self, page: &str) -> Vec<u8> {
+		match &self.content {
+			BodyContent::Form(fields) => {
+				let fields: Vec<(&str, String)> = fields.iter().map(|(name, value)| (name.as_str(), value.replace(PAGE_PLACEHOLDER, page))).collect();
+				serde_urlencoded::to_string(fields).unwrap_or_default().into_bytes()
+			},
+			BodyContent::Json(json) => render_json(json, page).to_string().into_bytes(),
+			BodyContent::Raw(raw) => raw.replace(PAGE_PLACEHOLDER, page).into_bytes(),
+		}
+	}
+}
+
+/// Replaces the placeholder in every string of the JSON. A string that is nothing but the placeholder becomes a number
+/// when the page is one, so page numbers keep the type APIs expect.
+fn render_json(json: &Value, page: &str) -> Value {
+	match json {
+		Value::String(text) if text == PAGE_PLACEHOLDER => match page.parse::<i64>() {
+			Ok(number) => Value::from(number),
+			Err(_) => Value::String(page.to_string()),
+		},
+		Value::String(text) => Value::String(text.replace(PAGE_PLACEHOLDER, page)),
+		Value::Array(values) => Value::Array(values.iter().map(|value| render_json(value, page)).collect()),
+		Value::Object(fields) => Value::Object(fields.iter().map(|(name, value)| (name.clone(), render_json(value, page))).collect()),
+		value => value.clone(),
+	}
+}
@@ -0,0 +1,84 @@
+use std::{sync::Mutex, time::Duration};
+
+use reqwest::{Client, Proxy};
+
+use super::tls::TlsConfig;
+use crate::error::{Error, Result};
+
+const DEFAULT_USER_AGENT: &str = concat!("breach-tracker/", env!("CARGO_PKG_VERSION"), " (+https://github.com/emagers/breach-tracker)");
+
+/// `[http]` settings shared by the clients of every source
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpConfig {
+	pub connect_timeout: Duration,
+	/// Longest wait for the response to start or for the next part of its body
+	pub read_timeout: Duration,
+	/// Longest a whole request may take, including reading the body
+	pub timeout: Option<Duration>,
+	/// Responses with a larger body fail instead of being read into memory
+	pub max_response_size: u64,
+	/// Sent with every request unless a source sets its own, should tell site owners how to reach whoever runs the scraper
+	pub user_agent: String,
+	/// HTTP, HTTPS or SOCKS proxy every request goes through
+	pub proxy: Option<String>,
+	/// How long an idle pooled connection is kept open
+	pub pool_idle_timeout: Duration,
+}
+
+impl Default for HttpConfig {
+	fn default() -> Self {
+		Self {
+			connect_timeout: Duration::from_secs(10),
+			read_timeout: Duration::from_secs(30),
+			timeout: None,
+			max_response_size: 50 * 1024 * 1024,
+			user_agent: DEFAULT_USER_AGENT.to_string(),
+			proxy: None,
+			pool_idle_timeout: Duration::from_secs(90),
+		}
+	}
+}
+
+/// Builds the clients of a run from the shared http settings and each source's TLS settings. Sources with the same TLS
+/// settings share one client, and with it its connection pool.
+pub struct ClientFactory {
+	config: HttpConfig,
+	clients: Mutex<Vec<(TlsConfig, Client)>>,
+}
+
+impl ClientFactory {
+	pub fn new(config: HttpConfig) -> ClientFactory {
+		ClientFactory { config, clients: Mutex::new(vec!()) }
+	}
+
+	pub fn config(&self) -> &HttpConfig {
+		&self.config
+	}
+
+	pub fn client(&self, tls: &TlsConfig) -> Result<Client> {
+		let mut clients = self.clients.lock().unwrap();
+		if let Some((_, client)) = clients.iter().find(|(config, _)| config == tls) {
+			return Ok(client.clone());
+		}
+
+		let mut builder = tls.apply(Client::builder())?
+			.user_agent(&self.config.user_agent)
+			.connect_timeout(self.config.connect_timeout)
+			.pool_idle_timeout(self.config.pool_idle_timeout)
+			.gzip(true)
+			.brotli(true);
+
+		if let Some(timeout) = self.config.timeout {
+			builder = builder.timeout(timeout);
+		}
+
+		if let Some(proxy) = &self.config.proxy {
+			builder = builder.proxy(Proxy::all(proxy).map_err(|err| Error::config(format!("invalid proxy {}: {}", proxy, err)))?);
+		}
+
+		let client = builder.build().map_err(|err| Error::config(format!("could not create http client: {}", err)))?;
+		clients.push((tls.clone(), client.clone()));
+
+		Ok(client)
+	}
+}
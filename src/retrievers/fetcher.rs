@@ -0,0 +1,157 @@
+use std::{collections::HashMap, io, sync::Arc, time::Instant};
+
+use reqwest::{Client, Url};
+
+use crate::dto::State;
+
+use super::{FetchError, RawResponse, read_body, client::HttpConfig, RetrieverOptions, archive::{Archive, FetchedPage, PageRecorder, content_hash}, replay::Snapshots, warc::WarcWriter, politeness::{Hosts, PolitenessPolicy, RobotsTxt}};
+
+/// The http clients of a run along with everything that keeps them polite, shared by every retriever of the run
+pub struct Fetcher {
+	/// Client of every source, built from its TLS settings
+	clients: HashMap<State, Client>,
+	/// Client of sources without one of their own
+	client: Client,
+	http: HttpConfig,
+	hosts: Arc<Hosts>,
+	archive: Option<(Archive, Box<dyn PageRecorder>)>,
+	replay: Option<Arc<Snapshots>>,
+	warc: Option<Arc<WarcWriter>>,
+}
+
+impl Fetcher {
+	pub fn new(clients: HashMap<State, Client>, client: Client, http: HttpConfig, hosts: Arc<Hosts>) -> Fetcher {
+		Fetcher { clients, client, http, hosts, archive: None, replay: None, warc: None }
+	}
+
+	/// Fetcher with the clients of this one that never touches the network, every url is served from the snapshots
+	pub fn replaying(&self, snapshots: Arc<Snapshots>) -> Fetcher {
+		let mut fetcher = Fetcher::new(self.clients.clone(), self.client.clone(), self.http.clone(), Arc::new(Hosts::new(PolitenessPolicy::default())));
+		fetcher.replay = Some(snapshots);
+		fetcher
+	}
+
+	/// Stores every response body in the archive and hands what is known about it to the recorder
+	pub fn with_archive(mut self, archive: Archive, recorder: Box<dyn PageRecorder>) -> Fetcher {
+		self.archive = Some((archive, recorder));
+		self
+	}
+
+	/// Writes every request and response to the WARC file as well
+	pub fn with_warc(mut self, warc: Arc<WarcWriter>) -> Fetcher {
+		self.warc = Some(warc);
+		self
+	}
+
+	pub fn client(&self, state: State) -> &Client {
+		self.clients.get(&state).unwrap_or(&self.client)
+	}
+
+	pub fn http(&self) -> &HttpConfig {
+		&self.http
+	}
+
+	pub fn snapshots(&self) -> Option<&Snapshots> {
+		self.replay.as_deref()
+	}
+
+	/// Waits until the host of the url may be requested again, failing when its robots.txt disallows the url
+	pub async fn wait_turn(&self, state: State, url: &str, user_agent: &str) -> Result<(), FetchError> {
+		// Urls that do not parse are left for the request to report
+		let Ok(url) = Url::parse(url) else { return Ok(()) };
+		let Some(host) = url.host_str() else { return Ok(()) };
+
+		let mut crawl_delay = None;
+		if self.hosts.limiter().policy().robots {
+			let robots = self.robots(self.client(state), &url, user_agent).await;
+
+			let path = match url.query() {
+				Some(query) => format!("{}?{}", url.path(), query),
+				None => url.path().to_string(),
+			};
+			if !robots.is_allowed(user_agent, &path) {
+				return Err(FetchError::Disallowed);
+			}
+
+			crawl_delay = robots.crawl_delay(user_agent);
+		}
+
+		self.hosts.limiter().wait(host, crawl_delay).await;
+
+		Ok(())
+	}
+
+	/// Records the response in the WARC file and archive, whichever the fetcher has
+	pub async fn archive(&self, options: &RetrieverOptions, response: &RawResponse) -> io::Result<()> {
+		if let Some(warc) = &self.warc {
+			warc.write(options, response)?;
+		}
+
+		let Some((archive, recorder)) = &self.archive else { return Ok(()) };
+
+		let body_hash = archive.store(&response.body)?;
+
+		recorder.record(FetchedPage {
+			state: options.state,
+			run_id: options.run_id,
+			url: response.url.clone(),
+			method: options.request_type.method().to_string(),
+			request_headers: response.request_headers.clone(),
+			status: response.status,
+			response_headers: response.headers.clone(),
+			fetched_at: response.fetched_at,
+			content_hash: body_hash,
+			size: response.body.len(),
+			request_body_hash: content_hash(&response.request_body),
+		}).await;
+
+		Ok(())
+	}
+
+	/// robots.txt of the url's host, fetched once a day. A missing or unreachable robots.txt allows everything, one the
+	/// server fails to serve disallows everything like RFC 9309 asks.
+	async fn robots(&self, client: &Client, url: &Url, user_agent: &str) -> Arc<RobotsTxt> {
+		let origin = url.origin().ascii_serialization();
+		let cell = self.hosts.robots(&origin);
+
+		// Concurrent requests to a new host wait for the one fetch of its robots.txt, requests to other hosts go on
+		let (_, robots) = cell.get_or_init(|| async { (Instant::now(), self.fetch_robots(client, url, &origin, user_agent).await) }).await;
+		robots.clone()
+	}
+
+	async fn fetch_robots(&self, client: &Client, url: &Url, origin: &str, user_agent: &str) -> Arc<RobotsTxt> {
+		self.hosts.limiter().wait(url.host_str().unwrap_or_default(), None).await;
+
+		let robots_url = format!("{}/robots.txt", origin);
+		let request = client.get(&robots_url).header(reqwest::header::USER_AGENT, user_agent).send();
+		let response = match tokio::time::timeout(self.http.read_timeout, request).await {
+			Ok(response) => response.map_err(FetchError::Request),
+			Err(_) => Err(FetchError::Timeout),
+		};
+
+		let rules = match response {
+			Ok(response) if response.status().is_success() => match read_body(response, &self.http).await {
+				Ok(body) => RobotsTxt::parse(&String::from_utf8_lossy(&body)),
+				Err(err) => {
+					eprintln!("Could not read {}: {}, continuing as if everything is allowed", robots_url, err);
+					RobotsTxt::default()
+				},
+			},
+			Ok(response) if response.status().is_server_error() => {
+				eprintln!("Could not fetch {}, it responded {}, continuing as if everything is disallowed", robots_url, response.status());
+				RobotsTxt::disallow_all()
+			},
+			Ok(response) if response.status().is_client_error() => RobotsTxt::default(),
+			Ok(response) => {
+				eprintln!("Could not fetch {}, it responded {}, continuing as if everything is allowed", robots_url, response.status());
+				RobotsTxt::default()
+			},
+			Err(err) => {
+				eprintln!("Could not fetch {}: {}, continuing as if everything is allowed", robots_url, err);
+				RobotsTxt::default()
+			},
+		};
+
+		Arc::new(rules)
+	}
+}
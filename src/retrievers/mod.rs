@@ -1,58 +1,421 @@
 pub mod single_page;
 pub mod multi_page;
+pub mod archive;
+pub mod body;
+pub mod pagination;
+pub mod session;
+pub mod sharepoint;
+pub mod replay;
+pub mod warc;
+pub mod fetcher;
+pub mod politeness;
+pub mod tls;
+pub mod client;
 
-use crate::{dto::Breach, parsers::Parser};
-use std::error::Error;
-use chrono::NaiveDateTime;
-use reqwest::{Client, header::{HeaderMap, HeaderValue}};
+use std::{future::Future, time::Duration};
+
+use crate::{dto::{Breach, State}, error::{Error, Result}, parsers::Parser};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use encoding_rs::{Encoding, UTF_8};
+use rand::Rng;
+use reqwest::{Client, Response, StatusCode, Version, header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER, USER_AGENT}};
 use async_trait::async_trait;
+use fetcher::Fetcher;
+use body::RequestBody;
+use pagination::Pagination;
+use client::HttpConfig;
+
+#[async_trait]
+pub trait Retriever: Send + Sync {
+	/// Retrieves pages starting at `options.start`, handing every page to the sink as soon as it is parsed so nothing
+	/// more than one page is held at a time
+	async fn retrieve(&self, fetcher: &Fetcher, parser: Box<dyn Parser + Send>, options: &RetrieverOptions, sink: &dyn PageSink) -> Result<Retrieval>;
+}
 
+/// Receives the breaches of every page along with where retrieval continues after that page
 #[async_trait]
-pub trait Retriever {
-	async fn retrieve(&self, client: &Client, parser: Box<dyn Parser + Send>, options: &RetrieverOptions, page_incrementer: Box<dyn Fn(i32) -> i32 + Send>, url_generator: Box<dyn Fn(String, String) -> String + Send>) -> Result<Vec<Breach>, Box<dyn std::error::Error>>;
+pub trait PageSink: Send + Sync {
+	async fn page(&self, options: &RetrieverOptions, breaches: Vec<Breach>, next: PageCursor) -> Result<StoredPage>;
+}
+
+/// Which breaches of a page were already stored before it, in the order of the page
+#[derive(Debug, Default)]
+pub struct StoredPage {
+	pub known: Vec<bool>,
+}
+
+/// Position of a paged retrieval, the page number and the url part the parser returned for the next page
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageCursor {
+	pub page: i32,
+	pub next_url_part: Option<String>,
+	/// Group of a SharePoint list the page is in, its pages are numbered within the group
+	pub group: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Retrieval {
+	pub pages_fetched: usize,
+	/// Every page responded that it has not changed since the validators it was sent
+	pub unchanged: bool,
+	/// Validators of the fetched urls, to be stored along with their breaches
+	pub validators: Vec<(String, Validators)>,
+}
+
+/// ETag and Last-Modified of a response, sent back as If-None-Match and If-Modified-Since so a page that did not change
+/// is answered with 304 Not Modified instead of being downloaded again
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validators {
+	pub etag: Option<String>,
+	pub last_modified: Option<String>,
 }
 
+impl Validators {
+	fn from_headers(headers: &HeaderMap<HeaderValue>) -> Validators {
+		let value = |name| headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok()).map(|value| value.to_string());
+
+		Validators { etag: value(ETAG), last_modified: value(LAST_MODIFIED) }
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.etag.is_none() && self.last_modified.is_none()
+	}
+
+	fn apply(&self, headers: &mut HeaderMap<HeaderValue>) {
+		if let Some(etag) = self.etag.as_ref().and_then(|etag| HeaderValue::from_str(etag).ok()) {
+			headers.insert(IF_NONE_MATCH, etag);
+		}
+		if let Some(last_modified) = self.last_modified.as_ref().and_then(|last_modified| HeaderValue::from_str(last_modified).ok()) {
+			headers.insert(IF_MODIFIED_SINCE, last_modified);
+		}
+	}
+}
+
+/// A response as it was received, before its status is checked
 #[derive(Debug)]
+pub struct RawResponse {
+	pub url: String,
+	/// Headers the request was sent with, besides those the client adds itself
+	pub request_headers: HeaderMap,
+	pub request_body: Vec<u8>,
+	pub fetched_at: NaiveDateTime,
+	pub version: Version,
+	pub status: StatusCode,
+	pub headers: HeaderMap,
+	pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
 pub enum WebRequestType {
-	Post,
+	/// A POST with the given body, or an empty one
+	Post(Option<RequestBody>),
 	Get,
 }
 
-#[derive(Debug)]
+impl WebRequestType {
+	pub fn method(&self) -> &'static str {
+		match self {
+			WebRequestType::Post(_) => "POST",
+			WebRequestType::Get => "GET",
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
 pub struct RetrieverOptions {
 	pub collect_until: NaiveDateTime,
+	/// Stop once this many breaches in a row were already stored, none to stop once a page reaches back past
+	/// `collect_until`
+	pub stop_after_known: Option<usize>,
 	pub base_url: String,
 	pub headers: HeaderMap<HeaderValue>,
-	pub state: crate::dto::State,
+	pub state: State,
 	pub request_type: WebRequestType,
+	pub start: PageCursor,
+	/// How retrievers that fetch more than one page move between pages and when they stop
+	pub pagination: Pagination,
+	pub retry: RetryPolicy,
+	/// Run the responses are archived under, none when they are not linked to a run
+	pub run_id: Option<i32>,
+	/// Validators of the last stored response of the url, only sent by retrievers that fetch a single page
+	pub validators: Option<Validators>,
+}
+
+/// How often and how long to wait before retrying a request that failed in a way that may pass
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+	pub max_retries: u32,
+	/// Wait before the first retry, doubled for every following retry
+	pub initial_backoff: Duration,
+	/// Longest wait between retries, a Retry-After asking for longer than this fails the request instead
+	pub max_backoff: Duration,
 }
 
-async fn invoke(client: &Client, url: &str, headers: &HeaderMap<HeaderValue>, request_type: &WebRequestType) -> Result<String, Box<dyn Error>> {
-	match request_type {
-		WebRequestType::Post => invoke_post(client, url, headers).await,
-		WebRequestType::Get => invoke_get(client, url, headers).await
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self {
+			max_retries: 3,
+			initial_backoff: Duration::from_secs(1),
+			max_backoff: Duration::from_secs(60),
+		}
 	}
 }
 
-async fn invoke_get(client: &Client, url: &str, headers: &HeaderMap<HeaderValue>) -> Result<String, Box<dyn Error>> {
-	let body = client.get(url)
-		.headers(headers.clone())
-		.send()
-		.await?
-		.text()
-		.await?;
+impl RetryPolicy {
+	/// Wait before the given retry, somewhere between half and all of the exponential backoff so sources that failed
+	/// together do not retry together
+	fn backoff(&self, retry: u32) -> Duration {
+		let backoff = self.initial_backoff.saturating_mul(2u32.saturating_pow(retry)).min(self.max_backoff);
+
+		backoff / 2 + backoff.mul_f64(rand::thread_rng().gen_range(0.0..=0.5))
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FetchError {
+	#[error(transparent)]
+	Request(#[from] reqwest::Error),
+	#[error("responded {status}")]
+	Status {
+		status: StatusCode,
+		retry_after: Option<Duration>,
+	},
+	#[error("disallowed by robots.txt")]
+	Disallowed,
+	#[error("could not archive the response: {0}")]
+	Archive(std::io::Error),
+	#[error("timed out waiting for the response")]
+	Timeout,
+	#[error("response is larger than {limit} bytes")]
+	TooLarge {
+		limit: u64,
+	},
+	#[error("no snapshot")]
+	NotArchived,
+	#[error("could not read the snapshot: {0}")]
+	Snapshot(std::io::Error),
+}
+
+impl FetchError {
+	pub fn is_retryable(&self) -> bool {
+		match self {
+			FetchError::Request(err) => err.is_timeout() || err.is_connect() || err.is_body(),
+			FetchError::Status { status, .. } => matches!(*status,
+				StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS | StatusCode::INTERNAL_SERVER_ERROR |
+				StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT),
+			FetchError::Timeout => true,
+			FetchError::Disallowed | FetchError::TooLarge { .. } | FetchError::Archive(_) | FetchError::NotArchived | FetchError::Snapshot(_) => false,
+		}
+	}
+
+	pub fn into_error(self, state: State, url: String, page: i32) -> Error {
+		match self {
+			FetchError::Request(source) => Error::Network { state, url, page, source },
+			FetchError::Status { status, .. } => Error::Status { state, url, page, status },
+			FetchError::Disallowed => Error::Disallowed { state, url },
+			FetchError::Timeout => Error::Timeout { state, url, page },
+			FetchError::TooLarge { limit } => Error::TooLarge { state, url, page, limit },
+			FetchError::Archive(source) => Error::Io { context: format!("{:?}: could not archive the response of {}", state, url), source },
+			FetchError::NotArchived => Error::NotArchived { state, url },
+			FetchError::Snapshot(source) => Error::Io { context: format!("{:?}: could not read the snapshot of {}", state, url), source },
+		}
+	}
+}
+
+/// Requests the url once its host may be requested again, retrying timeouts, dropped connections and error statuses
+/// that usually pass with exponential backoff. A Retry-After sent by the site is waited out instead of the backoff. A
+/// replaying fetcher serves the url from its snapshots instead. `page` fills the page placeholder of a POST body, it is
+/// the page number or next link the url was generated from.
+async fn invoke(fetcher: &Fetcher, url: &str, page: &str, options: &RetrieverOptions) -> std::result::Result<String, FetchError> {
+	Ok(invoke_conditional(fetcher, url, page, options, None).await?.map(|(text, _)| text).unwrap_or_default())
+}
+
+/// Like `invoke`, but sends the validators of an earlier response along. None when the site responds that the page did
+/// not change since, otherwise the page with its current validators.
+async fn invoke_conditional(fetcher: &Fetcher, url: &str, page: &str, options: &RetrieverOptions, validators: Option<&Validators>) -> std::result::Result<Option<(String, Validators)>, FetchError> {
+	Ok(invoke_with_headers(fetcher, url, page, options, validators).await?.map(|(text, headers)| (text, Validators::from_headers(&headers))))
+}
+
+/// Like `invoke_conditional`, but with all headers of the response. Snapshots have none.
+async fn invoke_with_headers(fetcher: &Fetcher, url: &str, page: &str, options: &RetrieverOptions, validators: Option<&Validators>) -> std::result::Result<Option<(String, HeaderMap)>, FetchError> {
+	if let Some(snapshots) = fetcher.snapshots() {
+		let body = request_body(options, page, &mut options.headers.clone());
+		return snapshots.load(options.request_type.method(), url, &body).map(|text| Some((text, HeaderMap::new())));
+	}
+
+	with_retries(fetcher, url, options, || invoke_once(fetcher, url, page, options, validators)).await
+}
+
+/// Downloads the url as it is, without decoding its body, retrying like `invoke` does. Used for the documents breaches
+/// link to, which are not pages of the source.
+pub async fn download(fetcher: &Fetcher, url: &str, options: &RetrieverOptions) -> std::result::Result<RawResponse, FetchError> {
+	with_retries(fetcher, url, options, || async {
+		let response = send(fetcher, url, "", options, options.headers.clone()).await?;
+		if !response.status.is_success() {
+			return Err(FetchError::Status { status: response.status, retry_after: retry_after(&response.headers) });
+		}
+
+		Ok(response)
+	}).await
+}
+
+/// Runs the attempt until it succeeds, fails in a way that does not pass or runs out of retries, waiting for the turn
+/// of the url's host before every attempt
+async fn with_retries<T, F, Fut>(fetcher: &Fetcher, url: &str, options: &RetrieverOptions, attempt: F) -> std::result::Result<T, FetchError>
+where
+	F: Fn() -> Fut,
+	Fut: Future<Output = std::result::Result<T, FetchError>>,
+{
+	let user_agent = options.headers.get(USER_AGENT).and_then(|value| value.to_str().ok()).unwrap_or(&fetcher.http().user_agent);
+	let mut retry = 0;
+
+	loop {
+		fetcher.wait_turn(options.state, url, user_agent).await?;
+
+		let err = match attempt().await {
+			Ok(fetched) => return Ok(fetched),
+			Err(err) => err,
+		};
+
+		if retry >= options.retry.max_retries || !err.is_retryable() {
+			return Err(err);
+		}
+
+		let wait = match &err {
+			FetchError::Status { retry_after: Some(retry_after), .. } if *retry_after > options.retry.max_backoff => return Err(err),
+			FetchError::Status { retry_after: Some(retry_after), .. } => *retry_after,
+			_ => options.retry.backoff(retry),
+		};
+
+		retry += 1;
+		eprintln!("{:?}: {} {}, retry {} of {} in {:?}", options.state, url, err, retry, options.retry.max_retries, wait);
+		tokio::time::sleep(wait).await;
+	}
+}
+
+async fn invoke_once(fetcher: &Fetcher, url: &str, page: &str, options: &RetrieverOptions, validators: Option<&Validators>) -> std::result::Result<Option<(String, HeaderMap)>, FetchError> {
+	let mut headers = options.headers.clone();
+	if let Some(validators) = validators {
+		validators.apply(&mut headers);
+	}
+
+	let response = send(fetcher, url, page, options, headers).await?;
+
+	if response.status == StatusCode::NOT_MODIFIED && validators.is_some() {
+		return Ok(None);
+	}
+
+	if !response.status.is_success() {
+		return Err(FetchError::Status { status: response.status, retry_after: retry_after(&response.headers) });
+	}
+
+	Ok(Some((decode(&response.headers, &response.body), response.headers)))
+}
+
+/// Sends one request and reads and archives its response, whatever its status
+async fn send(fetcher: &Fetcher, url: &str, page: &str, options: &RetrieverOptions, mut headers: HeaderMap<HeaderValue>) -> std::result::Result<RawResponse, FetchError> {
+	let http = fetcher.http();
+	let body = request_body(options, page, &mut headers);
+
+	let fetched_at = Utc::now().naive_utc();
+	let request = async {
+		match options.request_type {
+			WebRequestType::Post(_) => invoke_post(fetcher.client(options.state), url, &headers, body.clone()).await,
+			WebRequestType::Get => invoke_get(fetcher.client(options.state), url, &headers).await,
+		}
+	};
+	let response = tokio::time::timeout(http.read_timeout, request).await.map_err(|_| FetchError::Timeout)??;
+
+	let response = RawResponse {
+		url: url.to_string(),
+		request_headers: headers,
+		request_body: body,
+		fetched_at,
+		version: response.version(),
+		status: response.status(),
+		headers: response.headers().clone(),
+		body: read_body(response, http).await?,
+	};
+
+	// Error pages are archived too, they are as much what the site published as the data is
+	fetcher.archive(options, &response).await.map_err(FetchError::Archive)?;
+
+	Ok(response)
+}
+
+/// Body of the request for the page, adding the headers that describe it
+fn request_body(options: &RetrieverOptions, page: &str, headers: &mut HeaderMap<HeaderValue>) -> Vec<u8> {
+	match &options.request_type {
+		WebRequestType::Post(Some(body)) => {
+			if let Ok(content_type) = HeaderValue::from_str(&body.content_type) {
+				headers.entry(CONTENT_TYPE).or_insert(content_type);
+			}
+
+			body.render(page)
+		},
+		// The client leaves the length out of an empty body, some servers reject a POST without one
+		WebRequestType::Post(None) => {
+			headers.entry(CONTENT_LENGTH).or_insert(HeaderValue::from_static("0"));
+			vec!()
+		},
+		WebRequestType::Get => vec!(),
+	}
+}
+
+/// Reads the body a chunk at a time, failing as soon as it grows past the maximum size or stalls for longer than the
+/// read timeout
+async fn read_body(mut response: Response, http: &HttpConfig) -> std::result::Result<Vec<u8>, FetchError> {
+	let limit = http.max_response_size;
+	if response.content_length().is_some_and(|length| length > limit) {
+		return Err(FetchError::TooLarge { limit });
+	}
+
+	let mut body = vec!();
+	while let Some(chunk) = tokio::time::timeout(http.read_timeout, response.chunk()).await.map_err(|_| FetchError::Timeout)?? {
+		if (body.len() + chunk.len()) as u64 > limit {
+			return Err(FetchError::TooLarge { limit });
+		}
+
+		body.extend_from_slice(&chunk);
+	}
 
 	Ok(body)
 }
 
-async fn invoke_post(client: &Client, url: &str, headers: &HeaderMap<HeaderValue>) -> Result<String, Box<dyn Error>> {
-	let body = client.post(url)
+async fn invoke_get(client: &Client, url: &str, headers: &HeaderMap<HeaderValue>) -> reqwest::Result<Response> {
+	client.get(url)
 		.headers(headers.clone())
 		.send()
-		.await?
-		.text()
-		.await?;
+		.await
+}
 
-	Ok(body)
+async fn invoke_post(client: &Client, url: &str, headers: &HeaderMap<HeaderValue>, body: Vec<u8>) -> reqwest::Result<Response> {
+	client.post(url)
+		.headers(headers.clone())
+		.body(body)
+		.send()
+		.await
 }
 
+/// Decodes the body with the charset of its content type, like `Response::text` does
+fn decode(headers: &HeaderMap<HeaderValue>, body: &[u8]) -> String {
+	let encoding = headers.get(CONTENT_TYPE)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.split(';').find_map(|param| param.trim().strip_prefix("charset=")))
+		.and_then(|charset| Encoding::for_label(charset.trim_matches('"').as_bytes()))
+		.unwrap_or(UTF_8);
+
+	encoding.decode(body).0.into_owned()
+}
+
+/// Retry-After in either of its forms, a number of seconds or an HTTP date
+fn retry_after(headers: &HeaderMap<HeaderValue>) -> Option<Duration> {
+	let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+	if let Ok(seconds) = value.parse::<u64>() {
+		return Some(Duration::from_secs(seconds));
+	}
+
+	let date = DateTime::parse_from_rfc2822(value).ok()?;
+	Some((date.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default())
+}
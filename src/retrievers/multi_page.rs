@@ -1,54 +1,92 @@
-use reqwest::Client;
-use super::{Retriever, RetrieverOptions, invoke};
-use crate::{dto::{Breach}, parsers::{Parser}};
+use std::collections::HashSet;
+
+use chrono::NaiveDateTime;
+use super::{Retriever, fetcher::Fetcher, RetrieverOptions, Retrieval, PageSink, invoke, session::{Session, SessionConfig}};
+use crate::{error::{Error, Result}, parsers::{Parser}};
 use async_trait::async_trait;
 
-pub struct MultiPage {}
+/// Retrieves pages one after the other as the source's pagination describes, within a session when one is configured
+pub struct MultiPage {
+	session: Option<SessionConfig>,
+}
 
-#[async_trait]
-impl Retriever for MultiPage {
-	async fn retrieve(&self, client: &Client, parser: Box<dyn Parser + Send>, options: &RetrieverOptions, page_incrementer: Box<dyn Fn(i32) -> i32 + Send>, url_generator: Box<dyn Fn(String, String) -> String + Send>) -> Result<Vec<Breach>, Box<dyn std::error::Error>> {
-		let mut page = 0;
-		let mut next_url_part: Option<String> = None;
-		let mut continue_processing = true;
-
-		let mut breaches = vec!();
-
-		while continue_processing {
-			let next_url = match &next_url_part {
-				Some(part) => {
-					url_generator(options.base_url.clone(), part.clone())
-				},
-				None => url_generator(options.base_url.clone(), page.to_string())
-			};
+impl MultiPage {
+	pub fn new(session: Option<SessionConfig>) -> MultiPage {
+		MultiPage { session }
+	}
 
-			let text = invoke(client, &next_url, &options.headers, &options.request_type).await?;
+	/// Retrieves the pages of `options`, for retrievers that page through several lists with the same parser
+	pub async fn paginate(&self, fetcher: &Fetcher, parser: &mut (dyn Parser + Send), options: &RetrieverOptions, sink: &dyn PageSink) -> Result<Retrieval> {
+		let pagination = &options.pagination;
+		let mut cursor = options.start.clone();
 
-			let (mut brs, nup) = parser.parse_page(&text)?;
+		let mut pages_fetched = 0;
+		let mut fetched_urls = HashSet::new();
+		let mut known_in_a_row = 0;
+		let mut last_rows: Option<Vec<(String, NaiveDateTime, Option<String>)>> = None;
 
-			continue_processing = brs.len() > 0 && brs.last().unwrap().date_reported > options.collect_until;
+		let mut session = self.session.clone().map(Session::new);
+		if let Some(session) = session.as_mut() {
+			session.start(fetcher, options).await
+				.map_err(|err| err.into_error(options.state, options.base_url.clone(), cursor.page))?;
+		}
 
-			if breaches.len() > 0 && brs.len() > 0 {
-				let last_inserted: &Breach = breaches.last().unwrap();
-				let last_parsed = brs.last().unwrap();
-				if last_inserted.organization_name == last_parsed.organization_name && last_inserted.date_reported == last_parsed.date_reported && last_inserted.link == last_parsed.link {
-					break;
-				}
+		loop {
+			let url = pagination.url(&options.base_url, &cursor);
+			// Fills the page placeholder of posted bodies and fields, even for a first page requested from the bare url
+			let page = cursor.next_url_part.clone().unwrap_or_else(|| cursor.page.to_string());
+			if pages_fetched >= pagination.max_pages {
+				return Err(Error::TooManyPages { state: options.state, url, max_pages: pagination.max_pages });
 			}
 
-			breaches.append(&mut brs);
+			let fetched = match session.as_mut() {
+				Some(session) => session.invoke(fetcher, &url, &page, options).await,
+				None => invoke(fetcher, &url, &page, options).await,
+			};
+			let text = fetched.map_err(|err| err.into_error(options.state, url.clone(), cursor.page))?;
+			pages_fetched += 1;
+			fetched_urls.insert(url.clone());
 
-			page = page_incrementer(page);
+			let (brs, next_link) = parser.parse_page(&text)
+				.map_err(|source| Error::Parse { state: options.state, url: url.clone(), page: cursor.page, source })?;
 
-			if let Some(part) = &nup {
-				next_url_part = Some(part.clone());
+			// Sites serving their last page again for any page past it end here
+			let rows: Vec<_> = brs.iter().map(|breach| (breach.organization_name.clone(), breach.date_reported, breach.link.clone())).collect();
+			if !rows.is_empty() && last_rows.as_ref() == Some(&rows) {
+				break;
 			}
 
-			if nup.is_none() && next_url_part.is_some() {
+			let next = pagination.next(&cursor, next_link);
+			let past_collect_until = brs.last().is_none_or(|breach| breach.date_reported <= options.collect_until);
+			let last = brs.is_empty()
+				|| pagination.is_last(brs.len(), next.next_url_part.as_ref())
+				|| fetched_urls.contains(&pagination.url(&options.base_url, &next));
+
+			let stored = sink.page(options, brs, next.clone()).await?;
+
+			let caught_up = match options.stop_after_known {
+				Some(stop_after_known) => stored.known.iter().any(|known| {
+					known_in_a_row = if *known { known_in_a_row + 1 } else { 0 };
+					known_in_a_row >= stop_after_known
+				}),
+				None => past_collect_until,
+			};
+
+			if last || caught_up {
 				break;
 			}
+
+			cursor = next;
+			last_rows = Some(rows);
 		}
 
-		Ok(breaches)
+		Ok(Retrieval { pages_fetched, ..Default::default() })
+	}
+}
+
+#[async_trait]
+impl Retriever for MultiPage {
+	async fn retrieve(&self, fetcher: &Fetcher, mut parser: Box<dyn Parser + Send>, options: &RetrieverOptions, sink: &dyn PageSink) -> Result<Retrieval> {
+		self.paginate(fetcher, parser.as_mut(), options, sink).await
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,83 @@
+use super::PageCursor;
+
+/// Pages a single source may fetch in one retrieval unless its pagination sets `max_pages`
+pub const DEFAULT_MAX_PAGES: usize = 1000;
+
+/// How a multi page source moves from one page to the next and when it stops. Every kind stops on an empty page, a page
+/// repeating the one before it, a page reaching back past the collect until date or a url it already fetched, and fails
+/// once `max_pages` pages were fetched so a parser that no longer recognizes the last page cannot loop forever.
+#[derive(Debug, Clone)]
+pub struct Pagination {
+	pub kind: PaginationKind,
+	pub max_pages: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum PaginationKind {
+	/// Page numbers appended to the base url, from `start` in increments of `step`
+	PageNumber {
+		start: i32,
+		step: i32,
+		/// Request the base url as-is for the first page instead of appending its number
+		bare_first_page: bool,
+	},
+	/// Row offsets appended to the base url, from 0 in increments of `limit`. A page with fewer than `limit` rows is the
+	/// last one.
+	Offset {
+		limit: i32,
+		bare_first_page: bool,
+	},
+	/// The base url for the first page, then the base url with the next link the parser found on the page before
+	/// appended. A page without a next link is the last one.
+	NextLink,
+}
+
+impl Default for Pagination {
+	fn default() -> Self {
+		Self {
+			kind: PaginationKind::PageNumber { start: 0, step: 1, bare_first_page: false },
+			max_pages: DEFAULT_MAX_PAGES,
+		}
+	}
+}
+
+impl Pagination {
+	/// Where a retrieval without a checkpoint starts
+	pub fn start(&self) -> PageCursor {
+		match self.kind {
+			PaginationKind::PageNumber { start, .. } => PageCursor { page: start, ..Default::default() },
+			PaginationKind::Offset { .. } | PaginationKind::NextLink => PageCursor::default(),
+		}
+	}
+
+	/// Url of the page at the cursor
+	pub fn url(&self, base_url: &str, cursor: &PageCursor) -> String {
+		let part = match (&self.kind, &cursor.next_url_part) {
+			(PaginationKind::NextLink, Some(next_url_part)) => next_url_part.clone(),
+			(PaginationKind::NextLink, None) => String::new(),
+			(PaginationKind::PageNumber { start, bare_first_page: true, .. }, _) if cursor.page == *start => String::new(),
+			(PaginationKind::Offset { bare_first_page: true, .. }, _) if cursor.page == 0 => String::new(),
+			_ => cursor.page.to_string(),
+		};
+
+		format!("{}{}", base_url, part)
+	}
+
+	/// Cursor of the page after the one at `cursor`, given the next link the parser found on it
+	pub fn next(&self, cursor: &PageCursor, next_link: Option<String>) -> PageCursor {
+		match self.kind {
+			PaginationKind::PageNumber { step, .. } => PageCursor { page: cursor.page + step, next_url_part: None, group: cursor.group.clone() },
+			PaginationKind::Offset { limit, .. } => PageCursor { page: cursor.page + limit, next_url_part: None, group: cursor.group.clone() },
+			PaginationKind::NextLink => PageCursor { page: cursor.page + 1, next_url_part: next_link, group: cursor.group.clone() },
+		}
+	}
+
+	/// Whether a page with this many rows and next link is the last one, apart from the stops every kind shares
+	pub fn is_last(&self, rows: usize, next_link: Option<&String>) -> bool {
+		match self.kind {
+			PaginationKind::PageNumber { .. } => false,
+			PaginationKind::Offset { limit, .. } => rows < limit as usize,
+			PaginationKind::NextLink => next_link.is_none(),
+		}
+	}
+}
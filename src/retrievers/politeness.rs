@@ -0,0 +1,313 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use tokio::sync::OnceCell;
+
+/// How often a host may be requested, `burst` requests may go out back to back after the host was left alone a while
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+	pub min_delay: Duration,
+	pub burst: u32,
+}
+
+impl Default for RateLimit {
+	fn default() -> Self {
+		Self {
+			min_delay: Duration::from_secs(1),
+			burst: 1,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PolitenessPolicy {
+	pub rate_limit: RateLimit,
+	/// Rate limits of hosts that need a different one than `rate_limit`
+	pub hosts: HashMap<String, RateLimit>,
+	/// Fetch robots.txt of every host and refuse urls it disallows
+	pub robots: bool,
+}
+
+impl PolitenessPolicy {
+	fn rate_limit(&self, host: &str) -> &RateLimit {
+		self.hosts.get(host).unwrap_or(&self.rate_limit)
+	}
+}
+
+struct Bucket {
+	tokens: f64,
+	updated: Instant,
+}
+
+/// robots.txt is fetched again once it is older than this, like the day RFC 9309 allows it to be cached
+const ROBOTS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Token bucket per host, shared by every retriever of a run
+pub struct HostLimiter {
+	policy: PolitenessPolicy,
+	buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl HostLimiter {
+	pub fn new(policy: PolitenessPolicy) -> HostLimiter {
+		HostLimiter { policy, buckets: Mutex::new(HashMap::new()) }
+	}
+
+	pub fn policy(&self) -> &PolitenessPolicy {
+		&self.policy
+	}
+
+	/// Waits for the turn of the next request to the host. A crawl delay longer than the configured minimum delay wins.
+	pub async fn wait(&self, host: &str, crawl_delay: Option<Duration>) {
+		let rate_limit = self.policy.rate_limit(host);
+		let min_delay = crawl_delay.map_or(rate_limit.min_delay, |crawl_delay| crawl_delay.max(rate_limit.min_delay));
+		if min_delay.is_zero() {
+			return;
+		}
+
+		// Takes a token right away, going into debt when none is left, so concurrent requests queue up in order
+		let wait = {
+			let mut buckets = self.buckets.lock().unwrap();
+			let burst = rate_limit.burst.max(1) as f64;
+			let now = Instant::now();
+			let bucket = buckets.entry(host.to_string()).or_insert(Bucket { tokens: burst, updated: now });
+
+			bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() / min_delay.as_secs_f64()).min(burst);
+			bucket.updated = now;
+			bucket.tokens -= 1.0;
+
+			if bucket.tokens < 0.0 { min_delay.mul_f64(-bucket.tokens) } else { Duration::ZERO }
+		};
+
+		if !wait.is_zero() {
+			tokio::time::sleep(wait).await;
+		}
+	}
+}
+
+/// robots.txt of an origin along with when it was fetched, filled in by the first request to it
+type RobotsCell = Arc<OnceCell<(Instant, Arc<RobotsTxt>)>>;
+
+/// What is known of every host requested, its token bucket and robots.txt. Shared by the runs of the daemon so runs
+/// that follow one another wait on the same buckets.
+pub struct Hosts {
+	limiter: HostLimiter,
+	robots: Mutex<HashMap<String, RobotsCell>>,
+}
+
+impl Hosts {
+	pub fn new(policy: PolitenessPolicy) -> Hosts {
+		Hosts { limiter: HostLimiter::new(policy), robots: Mutex::new(HashMap::new()) }
+	}
+
+	pub fn limiter(&self) -> &HostLimiter {
+		&self.limiter
+	}
+
+	/// Cell holding the robots.txt of the origin, an empty one when it was not fetched yet or has expired
+	pub fn robots(&self, origin: &str) -> RobotsCell {
+		let mut robots = self.robots.lock().unwrap();
+		let cell = robots.entry(origin.to_string()).or_default();
+		if cell.get().is_some_and(|(fetched, _)| fetched.elapsed() > ROBOTS_TTL) {
+			*cell = Arc::default();
+		}
+
+		cell.clone()
+	}
+}
+
+/// The parts of a robots.txt that apply to us, the allow and disallow rules and crawl delay of every user agent group
+#[derive(Debug, Default)]
+pub struct RobotsTxt {
+	groups: Vec<RobotsGroup>,
+}
+
+#[derive(Debug, Default)]
+struct RobotsGroup {
+	agents: Vec<String>,
+	rules: Vec<(bool, String)>,
+	crawl_delay: Option<Duration>,
+}
+
+impl RobotsTxt {
+	/// Rules of a site whose robots.txt could not be fetched because of a server error
+	pub fn disallow_all() -> RobotsTxt {
+		RobotsTxt { groups: vec!(RobotsGroup { agents: vec!("*".to_string()), rules: vec!((false, "/".to_string())), crawl_delay: None }) }
+	}
+
+	pub fn parse(text: &str) -> RobotsTxt {
+		let mut groups: Vec<RobotsGroup> = vec!();
+		let mut in_agents = false;
+
+		for line in text.lines() {
+			let line = line.split('#').next().unwrap_or_default().trim();
+			let Some((field, value)) = line.split_once(':') else { continue };
+			let value = value.trim();
+
+			match field.trim().to_lowercase().as_str() {
+				"user-agent" => {
+					if !in_agents {
+						groups.push(RobotsGroup::default());
+					}
+					groups.last_mut().unwrap().agents.push(value.to_lowercase());
+					in_agents = true;
+				},
+				"allow" | "disallow" if !groups.is_empty() => {
+					// An empty disallow allows everything, which is the same as not having the rule
+					if !value.is_empty() {
+						groups.last_mut().unwrap().rules.push((field.trim().eq_ignore_ascii_case("allow"), value.to_string()));
+					}
+					in_agents = false;
+				},
+				"crawl-delay" if !groups.is_empty() => {
+					groups.last_mut().unwrap().crawl_delay = value.parse::<f64>().ok().filter(|delay| *delay >= 0.0).map(Duration::from_secs_f64);
+					in_agents = false;
+				},
+				_ => {},
+			}
+		}
+
+		RobotsTxt { groups }
+	}
+
+	/// Whether the path, including its query, may be fetched. The longest matching rule decides, allow wins a tie.
+	pub fn is_allowed(&self, user_agent: &str, path: &str) -> bool {
+		self.groups(user_agent).iter()
+			.flat_map(|group| group.rules.iter())
+			.filter(|(_, pattern)| matches(pattern, path))
+			.max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+			.is_none_or(|(allow, _)| *allow)
+	}
+
+	pub fn crawl_delay(&self, user_agent: &str) -> Option<Duration> {
+		self.groups(user_agent).iter().find_map(|group| group.crawl_delay)
+	}
+
+	/// Groups naming the product token of our user agent, the part before its version, or the * groups when none does.
+	/// Groups of the same agent count as one like RFC 9309 asks.
+	fn groups(&self, user_agent: &str) -> Vec<&RobotsGroup> {
+		let token = user_agent.split(['/', ' ']).next().unwrap_or_default().trim().to_lowercase();
+		let named = |name: &str| -> Vec<&RobotsGroup> {
+			self.groups.iter().filter(|group| group.agents.iter().any(|agent| agent == name)).collect()
+		};
+
+		let groups = if token.is_empty() { vec!() } else { named(&token) };
+		if groups.is_empty() { named("*") } else { groups }
+	}
+}
+
+/// Matches a robots.txt path pattern, where * matches anything and a trailing $ anchors the end of the path
+fn matches(pattern: &str, path: &str) -> bool {
+	let (pattern, anchored) = match pattern.strip_suffix('$') {
+		Some(pattern) => (pattern, true),
+		None => (pattern, false),
+	};
+
+	let mut parts = pattern.split('*');
+	let first = parts.next().unwrap_or_default();
+	let Some(mut rest) = path.strip_prefix(first) else { return false };
+
+	let parts: Vec<&str> = parts.collect();
+	for (i, part) in parts.iter().enumerate() {
+		// The last part of an anchored pattern has to match the end of the path, not its first occurrence
+		if anchored && i == parts.len() - 1 {
+			return rest.ends_with(part);
+		}
+
+		match rest.find(part) {
+			Some(index) => rest = &rest[index + part.len()..],
+			None => return false,
+		}
+	}
+
+	!anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const USER_AGENT: &str = "breach-tracker/0.1.0 (+https://github.com/emagers/breach-tracker)";
+
+	#[test]
+	fn picks_the_group_of_our_product_token() {
+		let robots = RobotsTxt::parse("User-agent: *\nDisallow: /all\n\nUser-agent: Breach-Tracker\nDisallow: /ours\n");
+
+		assert!(!robots.is_allowed(USER_AGENT, "/ours"));
+		assert!(robots.is_allowed(USER_AGENT, "/all"));
+		assert!(!robots.is_allowed("other-bot/1.0", "/all"));
+		assert!(robots.is_allowed("other-bot/1.0", "/ours"));
+	}
+
+	#[test]
+	fn ignores_agents_that_are_only_part_of_our_token() {
+		let robots = RobotsTxt::parse("User-agent: tracker\nDisallow: /\n\nUser-agent: *\nDisallow: /private\n");
+
+		assert!(robots.is_allowed(USER_AGENT, "/public"));
+		assert!(!robots.is_allowed(USER_AGENT, "/private"));
+	}
+
+	#[test]
+	fn combines_groups_of_the_same_agent() {
+		let robots = RobotsTxt::parse("User-agent: other\nUser-agent: breach-tracker\nDisallow: /a\n\nUser-agent: breach-tracker\nDisallow: /b\n");
+
+		assert!(!robots.is_allowed(USER_AGENT, "/a"));
+		assert!(!robots.is_allowed(USER_AGENT, "/b"));
+		assert!(robots.is_allowed(USER_AGENT, "/c"));
+	}
+
+	#[test]
+	fn allows_everything_without_a_matching_group() {
+		let robots = RobotsTxt::parse("User-agent: other\nDisallow: /\n");
+
+		assert!(robots.is_allowed(USER_AGENT, "/anything"));
+		assert!(RobotsTxt::default().is_allowed(USER_AGENT, "/anything"));
+	}
+
+	#[test]
+	fn longest_match_decides_and_allow_wins_a_tie() {
+		let robots = RobotsTxt::parse("User-agent: *\nDisallow: /list\nAllow: /list/public\nAllow: /same\nDisallow: /same\nDisallow:\n");
+
+		assert!(!robots.is_allowed(USER_AGENT, "/list/private"));
+		assert!(robots.is_allowed(USER_AGENT, "/list/public/1"));
+		assert!(robots.is_allowed(USER_AGENT, "/same"));
+		assert!(robots.is_allowed(USER_AGENT, "/other"));
+	}
+
+	#[test]
+	fn matches_wildcards_and_anchors() {
+		assert!(matches("/*.pdf", "/notices/a.pdf?download=1"));
+		assert!(!matches("/*.pdf$", "/notices/a.pdf?download=1"));
+		assert!(matches("/*.pdf$", "/notices/a.pdf"));
+		assert!(matches("/hi$", "/hi"));
+		assert!(!matches("/hi$", "/history"));
+		assert!(matches("/a*b*c", "/aXbYc/d"));
+		assert!(!matches("/a*b*c", "/aXcYb"));
+		assert!(matches("/a*c$", "/abcabc"));
+		assert!(!matches("/list", "/lis"));
+	}
+
+	#[test]
+	fn applies_patterns_to_the_query() {
+		let robots = RobotsTxt::parse("User-agent: *\nDisallow: /*?page=\n");
+
+		assert!(!robots.is_allowed(USER_AGENT, "/wa?page=2"));
+		assert!(robots.is_allowed(USER_AGENT, "/wa"));
+	}
+
+	#[test]
+	fn reads_the_crawl_delay_of_our_group() {
+		let robots = RobotsTxt::parse("User-agent: *\nCrawl-delay: 10\n\nUser-agent: breach-tracker # us\nCrawl-delay: 0.5\n\nUser-agent: other\nCrawl-delay: soon\n");
+
+		assert_eq!(robots.crawl_delay(USER_AGENT), Some(Duration::from_millis(500)));
+		assert_eq!(robots.crawl_delay("unknown/1.0"), Some(Duration::from_secs(10)));
+		assert_eq!(robots.crawl_delay("other/1.0"), None);
+	}
+
+	#[test]
+	fn disallow_all_disallows_every_path() {
+		let robots = RobotsTxt::disallow_all();
+
+		assert!(!robots.is_allowed(USER_AGENT, "/"));
+		assert!(!robots.is_allowed(USER_AGENT, "/robots.txt"));
+	}
+}
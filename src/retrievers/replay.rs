@@ -0,0 +1,136 @@
+use std::{collections::HashMap, fs, io, path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+
+use super::{Retriever, RetrieverOptions, Retrieval, PageSink, PageCursor, StoredPage, FetchError, archive::{Archive, content_hash}, decode, fetcher::Fetcher, warc::{self, WarcResponse}};
+use crate::{datamodels::FetchedPage, dto::Breach, error::{Error, Result}, parsers::Parser};
+
+/// Stored copies of pages, served in place of the network during a replay
+#[derive(Debug)]
+pub enum Snapshots {
+	/// Pages saved as files named after their url, see `file_name`. They hold no request, so every request of a url is
+	/// served the same page.
+	Directory(PathBuf),
+	/// Archived response bodies along with the content type they were served with, by `request_key`
+	Archive {
+		archive: Archive,
+		pages: HashMap<String, (String, Option<String>)>,
+	},
+	/// Responses captured in a WARC file, by `request_key`
+	Warc(HashMap<String, WarcResponse>),
+}
+
+impl Snapshots {
+	/// Snapshots of the archived pages, later pages of the same request replace earlier ones. Pages archived before
+	/// request bodies were recorded count as sent without one.
+	pub fn archived(archive: Archive, pages: Vec<FetchedPage>) -> Snapshots {
+		let pages = pages.into_iter().map(|page| {
+			let content_type = serde_json::from_str::<HashMap<String, String>>(&page.response_headers).ok()
+				.and_then(|mut headers| headers.remove(CONTENT_TYPE.as_str()));
+			let body_hash = page.request_body_hash.unwrap_or_else(|| content_hash(&[]));
+
+			(request_key(&page.method, &page.url, &body_hash), (page.content_hash, content_type))
+		}).collect();
+
+		Snapshots::Archive { archive, pages }
+	}
+
+	/// Snapshots of the responses of a WARC file, later captures of the same request replace earlier ones
+	pub fn warc(path: &Path) -> io::Result<Snapshots> {
+		let responses = warc::read_responses(path)?.into_iter()
+			.map(|response| (request_key(&response.method, &response.uri, &response.request_body_hash), response))
+			.collect();
+
+		Ok(Snapshots::Warc(responses))
+	}
+
+	/// Body of the response to the request decoded to text, `FetchError::NotArchived` when there is no snapshot of it
+	pub fn load(&self, method: &str, url: &str, body: &[u8]) -> std::result::Result<String, FetchError> {
+		let mut content_type = None;
+		let body = match self {
+			Snapshots::Directory(dir) => match fs::read(dir.join(file_name(url))) {
+				Ok(body) => body,
+				Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(FetchError::NotArchived),
+				Err(err) => return Err(FetchError::Snapshot(err)),
+			},
+			Snapshots::Archive { archive, pages } => {
+				let Some((hash, archived_content_type)) = pages.get(&request_key(method, url, &content_hash(body))) else { return Err(FetchError::NotArchived) };
+				content_type = archived_content_type.as_ref();
+
+				archive.load(hash).map_err(FetchError::Snapshot)?
+			},
+			Snapshots::Warc(responses) => {
+				let Some(response) = responses.get(&request_key(method, url, &content_hash(body))) else { return Err(FetchError::NotArchived) };
+				content_type = response.content_type.as_ref();
+
+				response.body.clone()
+			},
+		};
+
+		let mut headers = HeaderMap::new();
+		if let Some(content_type) = content_type.and_then(|content_type| HeaderValue::from_str(content_type).ok()) {
+			headers.insert(CONTENT_TYPE, content_type);
+		}
+
+		Ok(decode(&headers, &body))
+	}
+}
+
+/// Tells apart requests of the same url, like the postbacks of a session or the pages of a POST body
+fn request_key(method: &str, url: &str, body_hash: &str) -> String {
+	format!("{} {} {}", method.to_uppercase(), warc::normalize_uri(url), body_hash)
+}
+
+/// Name of the file a page is saved under in a snapshot directory, the url without its scheme and with everything but
+/// letters, digits, dots and dashes replaced by underscores
+pub fn file_name(url: &str) -> String {
+	let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+
+	url.chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' }).collect()
+}
+
+/// Runs a source's own retriever against snapshots instead of the network, so its pagination and parsing work exactly
+/// like they do when scraping
+pub struct Replay {
+	retriever: Box<dyn Retriever>,
+	snapshots: Arc<Snapshots>,
+}
+
+impl Replay {
+	pub fn new(retriever: Box<dyn Retriever>, snapshots: Arc<Snapshots>) -> Replay {
+		Replay { retriever, snapshots }
+	}
+}
+
+/// Counts the pages the replayed retriever parsed while handing them on to the caller's sink
+struct Counted<'a> {
+	sink: &'a dyn PageSink,
+	pages: AtomicUsize,
+}
+
+#[async_trait]
+impl PageSink for Counted<'_> {
+	async fn page(&self, options: &RetrieverOptions, breaches: Vec<Breach>, next: PageCursor) -> Result<StoredPage> {
+		self.pages.fetch_add(1, Ordering::Relaxed);
+
+		self.sink.page(options, breaches, next).await
+	}
+}
+
+#[async_trait]
+impl Retriever for Replay {
+	async fn retrieve(&self, fetcher: &Fetcher, parser: Box<dyn Parser + Send>, options: &RetrieverOptions, sink: &dyn PageSink) -> Result<Retrieval> {
+		let fetcher = fetcher.replaying(self.snapshots.clone());
+		let counted = Counted { sink, pages: AtomicUsize::new(0) };
+
+		match self.retriever.retrieve(&fetcher, parser, options, &counted).await {
+			Ok(_) => {},
+			// Snapshots only go as far as the run that stored them, running out of them after the first page ends the replay
+			Err(Error::NotArchived { .. }) if counted.pages.load(Ordering::Relaxed) > 0 => {},
+			Err(err) => return Err(err),
+		}
+
+		Ok(Retrieval { pages_fetched: counted.pages.into_inner(), ..Default::default() })
+	}
+}
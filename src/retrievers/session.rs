@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, HashMap};
+
+use reqwest::{Url, header::{HeaderMap, HeaderValue, COOKIE, SET_COOKIE}};
+
+use super::{FetchError, RetrieverOptions, WebRequestType, body::{BodyContent, RequestBody}, fetcher::Fetcher, invoke_with_headers};
+
+/// `[source.session]` settings, for sites that only serve their listing to a browser-like session such as ASP.NET and
+/// SharePoint postback pages
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+	/// Requested before the first page to start the session, the first page is posted back to it when set
+	pub start_url: Option<String>,
+	/// Posted along with the hidden fields of the previous response, replacing hidden fields of the same name. `{page}`
+	/// is replaced like it is in request bodies.
+	pub fields: Vec<(String, String)>,
+}
+
+/// Cookies, hidden form fields and form action carried from every response to the next request. The first request is
+/// sent the way the source is configured, every later one is posted back as a form.
+#[derive(Debug)]
+pub struct Session {
+	config: SessionConfig,
+	started: bool,
+	cookies: BTreeMap<String, String>,
+	hidden_fields: Vec<(String, String)>,
+	/// Url the form of the previous response posts to, which postback pages are requested from instead of the page url
+	action: Option<String>,
+}
+
+impl Session {
+	pub fn new(config: SessionConfig) -> Session {
+		Session { config, started: false, cookies: BTreeMap::new(), hidden_fields: vec!(), action: None }
+	}
+
+	/// Requests the start url, when there is one
+	pub async fn start(&mut self, fetcher: &Fetcher, options: &RetrieverOptions) -> Result<(), FetchError> {
+		if let Some(start_url) = self.config.start_url.clone() {
+			let mut request = self.request(options);
+			request.request_type = WebRequestType::Get;
+
+			self.invoke_as(fetcher, &start_url, "", &request).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Requests a page within the session, `page` fills the page placeholder of the posted fields
+	pub async fn invoke(&mut self, fetcher: &Fetcher, url: &str, page: &str, options: &RetrieverOptions) -> Result<String, FetchError> {
+		let request = self.request(options);
+		let url = self.action.clone().unwrap_or_else(|| url.to_string());
+
+		self.invoke_as(fetcher, &url, page, &request).await
+	}
+
+	async fn invoke_as(&mut self, fetcher: &Fetcher, url: &str, page: &str, request: &RetrieverOptions) -> Result<String, FetchError> {
+		let (text, headers) = invoke_with_headers(fetcher, url, page, request, None).await?.unwrap_or_default();
+		self.update(url, &headers, &text);
+
+		Ok(text)
+	}
+
+	/// The options with the session's cookies, and once the session started its fields posted as a form
+	fn request(&self, options: &RetrieverOptions) -> RetrieverOptions {
+		let mut request = options.clone();
+
+		if !self.cookies.is_empty() {
+			let session_cookies = self.cookies.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join("; ");
+			let cookies = match options.headers.get(COOKIE).and_then(|value| value.to_str().ok()) {
+				Some(configured) => format!("{}; {}", configured, session_cookies),
+				None => session_cookies,
+			};
+
+			if let Ok(cookies) = HeaderValue::from_str(&cookies) {
+				request.headers.insert(COOKIE, cookies);
+			}
+		}
+
+		if self.started {
+			let mut fields = self.hidden_fields.clone();
+			for (name, value) in self.config.fields.iter() {
+				fields.retain(|(hidden, _)| hidden != name);
+				fields.push((name.clone(), value.clone()));
+			}
+
+			let content = BodyContent::Form(fields);
+			request.request_type = WebRequestType::Post(Some(RequestBody { content_type: content.default_content_type().to_string(), content }));
+		}
+
+		request
+	}
+
+	/// Takes the cookies the response set and the hidden fields and form action of its page. Responses without a form,
+	/// like the JSON of SharePoint list views, keep the fields of the last page that had one.
+	fn update(&mut self, url: &str, headers: &HeaderMap, text: &str) {
+		self.started = true;
+
+		for cookie in headers.get_all(SET_COOKIE).iter().filter_map(|value| value.to_str().ok()) {
+			let mut attributes = cookie.split(';');
+			let Some((name, value)) = attributes.next().and_then(|pair| pair.split_once('=')) else { continue };
+			let expired = attributes.any(|attribute| attribute.trim().to_ascii_lowercase().strip_prefix("max-age=").is_some_and(|age| age.parse::<i64>().is_ok_and(|age| age <= 0)));
+
+			if expired {
+				self.cookies.remove(name.trim());
+			}
+			else {
+				self.cookies.insert(name.trim().to_string(), value.trim().to_string());
+			}
+		}
+
+		let forms = tags(text, "form");
+		let Some(form) = forms.first() else { return };
+
+		self.action = form.get("action")
+			.filter(|action| !action.is_empty())
+			.and_then(|action| Url::parse(url).and_then(|url| url.join(action)).ok())
+			.map(|action| action.to_string());
+		self.hidden_fields = tags(text, "input").into_iter()
+			.filter(|input| input.get("type").is_some_and(|kind| kind.eq_ignore_ascii_case("hidden")))
+			.filter_map(|mut input| Some((input.remove("name")?, input.remove("value").unwrap_or_default())))
+			.collect();
+	}
+}
+
+/// Attributes of every `<tag ...>` in the html, by lower case name with their values unescaped
+fn tags(html: &str, tag: &str) -> Vec<HashMap<String, String>> {
+	let lower = html.to_ascii_lowercase();
+	let open = format!("<{}", tag);
+	let mut found = vec!();
+	let mut rest = 0;
+
+	while let Some(start) = lower[rest..].find(&open).map(|start| rest + start + open.len()) {
+		let Some(end) = lower[start..].find('>').map(|end| start + end) else { break };
+		rest = end;
+
+		// `<formfoo` is not a form
+		if !html[start..].starts_with(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/') {
+			continue;
+		}
+
+		found.push(attributes(&html[start..end]));
+	}
+
+	found
+}
+
+fn attributes(mut text: &str) -> HashMap<String, String> {
+	let mut attributes = HashMap::new();
+
+	loop {
+		text = text.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+		let name_end = text.find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '/').unwrap_or(text.len());
+		if name_end == 0 {
+			return attributes;
+		}
+
+		let name = text[..name_end].to_ascii_lowercase();
+		text = text[name_end..].trim_start();
+
+		let Some(after_equals) = text.strip_prefix('=') else {
+			attributes.insert(name, String::new());
+			continue;
+		};
+		text = after_equals.trim_start();
+
+		let value = match text.chars().next() {
+			Some(quote) if quote == '"' || quote == '\'' => {
+				let end = text[1..].find(quote).map_or(text.len(), |end| end + 1);
+				let value = &text[1..end];
+				text = text.get(end + 1..).unwrap_or_default();
+				value
+			},
+			_ => {
+				let end = text.find(|c: char| c.is_ascii_whitespace()).unwrap_or(text.len());
+				let value = &text[..end];
+				text = &text[end..];
+				value
+			},
+		};
+
+		attributes.insert(name, unescape(value));
+	}
+}
+
+/// Replaces the character references ASP.NET and SharePoint use in attribute values
+fn unescape(value: &str) -> String {
+	let mut unescaped = String::with_capacity(value.len());
+	let mut rest = value;
+
+	while let Some(start) = rest.find('&') {
+		unescaped.push_str(&rest[..start]);
+		rest = &rest[start..];
+
+		let reference = rest.find(';').map(|end| (&rest[1..end], end));
+		let character = reference.and_then(|(name, _)| match name {
+			"amp" => Some('&'),
+			"lt" => Some('<'),
+			"gt" => Some('>'),
+			"quot" => Some('"'),
+			"apos" => Some('\''),
+			_ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+				Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+				None => name.strip_prefix('#').and_then(|decimal| decimal.parse().ok()).and_then(char::from_u32),
+			},
+		});
+
+		match (character, reference) {
+			(Some(character), Some((_, end))) => {
+				unescaped.push(character);
+				rest = &rest[end + 1..];
+			},
+			_ => {
+				unescaped.push('&');
+				rest = &rest[1..];
+			},
+		}
+	}
+
+	unescaped.push_str(rest);
+	unescaped
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PAGE: &str = r#"<!DOCTYPE html>
+<html><body>
+<FORM method="post" action="./Breaches.aspx?list=1&amp;view=all" id='form1'>
+<div class="aspNetHidden">
+<input type="hidden" name="__EVENTTARGET" id="__EVENTTARGET" value="" />
+<input type="hidden" name="__VIEWSTATE" id="__VIEWSTATE" value="/wEPDwUKMTY3&#43;NjQ5MjM0D2QWAmYPZBYC=" />
+<INPUT TYPE='hidden' NAME='__EVENTVALIDATION' VALUE='/wEdAAR&quot;x&#x27;y&lt;z&gt;' />
+<input type=hidden name=__VIEWSTATEGENERATOR value=CA0B0334>
+</div>
+<input type="text" name="search" value="breach">
+<formatted>not a form</formatted>
+</FORM>
+</body></html>"#;
+
+	#[test]
+	fn finds_tags_case_insensitively_and_skips_longer_names() {
+		let forms = tags(PAGE, "form");
+
+		assert_eq!(forms.len(), 1);
+		assert_eq!(forms[0]["method"], "post");
+		assert_eq!(forms[0]["id"], "form1");
+		assert_eq!(tags(PAGE, "input").len(), 5);
+	}
+
+	#[test]
+	fn reads_double_single_and_unquoted_attributes() {
+		let attributes = attributes(r#" type="hidden" NAME='a b' value=plain disabled data-x = "spaced" /"#);
+
+		assert_eq!(attributes["type"], "hidden");
+		assert_eq!(attributes["name"], "a b");
+		assert_eq!(attributes["value"], "plain");
+		assert_eq!(attributes["disabled"], "");
+		assert_eq!(attributes["data-x"], "spaced");
+	}
+
+	#[test]
+	fn keeps_quotes_of_the_other_kind_in_values() {
+		let attributes = attributes(r#"a="it's" b='say "hi"'"#);
+
+		assert_eq!(attributes["a"], "it's");
+		assert_eq!(attributes["b"], r#"say "hi""#);
+	}
+
+	#[test]
+	fn unescapes_named_and_numeric_references() {
+		assert_eq!(unescape("a&amp;b&lt;c&gt;d&quot;e&apos;f"), "a&b<c>d\"e'f");
+		assert_eq!(unescape("&#43;&#x2F;&#X2f;"), "+//");
+		assert_eq!(unescape("fish & chips &unknown; &#xZZ; &"), "fish & chips &unknown; &#xZZ; &");
+	}
+
+	#[test]
+	fn takes_hidden_fields_and_form_action_of_the_page() {
+		let mut session = Session::new(SessionConfig { start_url: None, fields: vec!() });
+		session.update("https://example.gov/notices/Breaches.aspx", &HeaderMap::new(), PAGE);
+
+		assert_eq!(session.action.as_deref(), Some("https://example.gov/notices/Breaches.aspx?list=1&view=all"));
+		assert_eq!(session.hidden_fields, vec!(
+			("__EVENTTARGET".to_string(), String::new()),
+			("__VIEWSTATE".to_string(), "/wEPDwUKMTY3+NjQ5MjM0D2QWAmYPZBYC=".to_string()),
+			("__EVENTVALIDATION".to_string(), "/wEdAAR\"x'y<z>".to_string()),
+			("__VIEWSTATEGENERATOR".to_string(), "CA0B0334".to_string()),
+		));
+	}
+
+	#[test]
+	fn keeps_the_fields_of_the_last_page_with_a_form_and_tracks_cookies() {
+		let mut session = Session::new(SessionConfig { start_url: None, fields: vec!() });
+		let mut headers = HeaderMap::new();
+		headers.append(SET_COOKIE, HeaderValue::from_static("ASP.NET_SessionId=abc; path=/; HttpOnly"));
+		headers.append(SET_COOKIE, HeaderValue::from_static("stale=1; Max-Age=60"));
+		session.update("https://example.gov/Breaches.aspx", &headers, PAGE);
+
+		let mut headers = HeaderMap::new();
+		headers.append(SET_COOKIE, HeaderValue::from_static("stale=; Max-Age=0"));
+		session.update("https://example.gov/_layouts/15/inplview.aspx", &headers, r#"{"Row": []}"#);
+
+		assert_eq!(session.hidden_fields.len(), 4);
+		assert_eq!(session.action.as_deref(), Some("https://example.gov/Breaches.aspx?list=1&view=all"));
+		assert_eq!(session.cookies, BTreeMap::from([("ASP.NET_SessionId".to_string(), "abc".to_string())]));
+	}
+}
@@ -0,0 +1,114 @@
+use async_trait::async_trait;
+
+use super::{Retriever, fetcher::Fetcher, RetrieverOptions, Retrieval, PageSink, PageCursor, StoredPage, invoke, multi_page::MultiPage, session::SessionConfig};
+use crate::{config::GROUP_PLACEHOLDER, dto::Breach, error::{Error, Result}, parsers::{Parser, ParseError, sharepoint::group_values}};
+
+/// `[source.sharepoint]` settings, for list views grouped by a field such as the year a notice was received
+#[derive(Debug, Clone)]
+pub struct SharePointConfig {
+	/// List view request with the groups collapsed, which lists a row for every group
+	pub groups_url: String,
+	/// Internal name of the field the view is grouped by
+	pub group_field: String,
+}
+
+/// Retrieves a grouped SharePoint list view one group at a time. The groups are discovered from the collapsed view on
+/// every retrieval, so a group the site adds is picked up without a config change, and each group is paged through
+/// with the NextHref of its pages.
+pub struct SharePointList {
+	config: SharePointConfig,
+	pages: MultiPage,
+}
+
+impl SharePointList {
+	pub fn new(config: SharePointConfig, session: Option<SessionConfig>) -> SharePointList {
+		SharePointList { config, pages: MultiPage::new(session) }
+	}
+
+	/// Groups in the order the collapsed view lists them and the number of pages it took
+	async fn groups(&self, fetcher: &Fetcher, options: &RetrieverOptions) -> Result<(Vec<String>, usize)> {
+		let mut groups: Vec<String> = vec!();
+		let mut next_url_part: Option<String> = None;
+		let mut pages_fetched = 0;
+
+		loop {
+			let url = format!("{}{}", self.config.groups_url, next_url_part.clone().unwrap_or_default());
+			let page = pages_fetched as i32;
+			if pages_fetched >= options.pagination.max_pages {
+				return Err(Error::TooManyPages { state: options.state, url, max_pages: options.pagination.max_pages });
+			}
+
+			let text = invoke(fetcher, &url, &next_url_part.clone().unwrap_or_else(|| page.to_string()), options).await
+				.map_err(|err| err.into_error(options.state, url.clone(), page))?;
+			pages_fetched += 1;
+
+			let (values, next) = group_values(&text, &self.config.group_field)
+				.map_err(|source| Error::Parse { state: options.state, url: url.clone(), page, source })?;
+			for value in values {
+				if !groups.contains(&value) {
+					groups.push(value);
+				}
+			}
+
+			if groups.is_empty() {
+				let source = ParseError::new(format!("no groups of {}", self.config.group_field), &text);
+				return Err(Error::Parse { state: options.state, url, page, source });
+			}
+
+			match next {
+				Some(next) if next_url_part.as_ref() != Some(&next) => next_url_part = Some(next),
+				_ => break,
+			}
+		}
+
+		Ok((groups, pages_fetched))
+	}
+}
+
+#[async_trait]
+impl Retriever for SharePointList {
+	async fn retrieve(&self, fetcher: &Fetcher, mut parser: Box<dyn Parser + Send>, options: &RetrieverOptions, sink: &dyn PageSink) -> Result<Retrieval> {
+		let (groups, mut pages_fetched) = self.groups(fetcher, options).await?;
+
+		// A backfill resumes in the group of its checkpoint, a group whose last page was stored has no next link left
+		let start = options.start.group.as_ref().and_then(|group| groups.iter().position(|listed| listed == group));
+		let (first, mut cursor) = match start {
+			Some(index) if options.start.next_url_part.is_none() && options.start.page > 0 => (index + 1, options.pagination.start()),
+			Some(index) => (index, PageCursor { group: None, ..options.start.clone() }),
+			None => (0, options.pagination.start()),
+		};
+
+		for group in groups.iter().skip(first) {
+			let mut group_options = options.clone();
+			group_options.base_url = options.base_url.replace(GROUP_PLACEHOLDER, &encode(group));
+			group_options.start = std::mem::replace(&mut cursor, options.pagination.start());
+
+			let grouped = Grouped { sink, options, group };
+			pages_fetched += self.pages.paginate(fetcher, parser.as_mut(), &group_options, &grouped).await?.pages_fetched;
+		}
+
+		Ok(Retrieval { pages_fetched, ..Default::default() })
+	}
+}
+
+/// Hands the pages of one group on as pages of the whole list, with the group in their cursor
+struct Grouped<'a> {
+	sink: &'a dyn PageSink,
+	options: &'a RetrieverOptions,
+	group: &'a str,
+}
+
+#[async_trait]
+impl PageSink for Grouped<'_> {
+	async fn page(&self, _: &RetrieverOptions, breaches: Vec<Breach>, next: PageCursor) -> Result<StoredPage> {
+		self.sink.page(self.options, breaches, PageCursor { group: Some(self.group.to_string()), ..next }).await
+	}
+}
+
+/// Percent encodes a group for the query of the base url
+fn encode(group: &str) -> String {
+	group.bytes().map(|byte| match byte {
+		b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+		_ => format!("%{:02X}", byte),
+	}).collect()
+}
@@ -1,22 +1,29 @@
-use reqwest::{Client};
-use super::{Retriever, RetrieverOptions, invoke};
-use crate::{dto::{Breach}, parsers::{Parser}};
+use super::{Retriever, fetcher::Fetcher, RetrieverOptions, Retrieval, PageSink, PageCursor, invoke_conditional};
+use crate::{error::{Error, Result}, parsers::{Parser}};
 use async_trait::async_trait;
 
 pub struct SinglePage {}
 
 #[async_trait]
 impl Retriever for SinglePage {
-	async fn retrieve(&self, client: &Client, parser: Box<dyn Parser + Send>, options: &RetrieverOptions, _: Box<dyn Fn(i32) -> i32 + Send>, url_generator: Box<dyn Fn(String, String) -> String + Send>) -> Result<Vec<Breach>, Box<dyn std::error::Error>> {
-		let mut breaches = vec!();
-		let next_url = url_generator(options.base_url.clone(), "".into());
+	async fn retrieve(&self, fetcher: &Fetcher, parser: Box<dyn Parser + Send>, options: &RetrieverOptions, sink: &dyn PageSink) -> Result<Retrieval> {
+		let next_url = options.base_url.clone();
 
-		let text = invoke(client, &next_url, &options.headers, &options.request_type).await?;
+		let fetched = invoke_conditional(fetcher, &next_url, "", options, options.validators.as_ref()).await
+			.map_err(|err| err.into_error(options.state, next_url.clone(), 0))?;
 
-		let (mut brs, _) = parser.parse_page(&text)?;
+		let Some((text, validators)) = fetched else {
+			eprintln!("{:?}: {} has not changed since the last run", options.state, next_url);
+			return Ok(Retrieval { pages_fetched: 1, unchanged: true, ..Default::default() });
+		};
 
-		breaches.append(&mut brs);
+		let (brs, _) = parser.parse_page(&text)
+			.map_err(|source| Error::Parse { state: options.state, url: next_url.clone(), page: 0, source })?;
 
-		Ok(breaches)
+		sink.page(options, brs, PageCursor { page: 1, ..Default::default() }).await?;
+
+		let validators = if validators.is_empty() { vec!() } else { vec!((next_url, validators)) };
+
+		Ok(Retrieval { pages_fetched: 1, unchanged: false, validators })
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,54 @@
+use std::{fs, path::PathBuf};
+
+use reqwest::{Certificate, ClientBuilder, tls::Version};
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TlsBackend {
+	/// The platform's TLS library, OpenSSL on Linux
+	Native,
+	Rustls,
+}
+
+/// TLS settings of one source, sources with different settings get a client of their own. Servers that do not support
+/// secure renegotiation (RFC 5746), which OpenSSL 3 refuses, need the rustls backend, it never renegotiates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsConfig {
+	pub backend: TlsBackend,
+	pub min_version: Option<Version>,
+	/// PEM files of certificate authorities trusted on top of the built in roots
+	pub ca_roots: Vec<PathBuf>,
+}
+
+impl Default for TlsConfig {
+	fn default() -> Self {
+		Self {
+			backend: TlsBackend::Native,
+			min_version: None,
+			ca_roots: vec!(),
+		}
+	}
+}
+
+impl TlsConfig {
+	/// Sets the backend, minimum version and extra roots on the builder
+	pub fn apply(&self, builder: ClientBuilder) -> Result<ClientBuilder> {
+		let mut builder = match self.backend {
+			TlsBackend::Native => builder.use_native_tls(),
+			TlsBackend::Rustls => builder.use_rustls_tls(),
+		};
+
+		if let Some(min_version) = self.min_version {
+			builder = builder.min_tls_version(min_version);
+		}
+
+		for path in self.ca_roots.iter() {
+			let pem = fs::read(path).map_err(|source| Error::Io { context: format!("could not read CA root {}", path.display()), source })?;
+			let certificate = Certificate::from_pem(&pem).map_err(|err| Error::config(format!("invalid CA root {}: {}", path.display(), err)))?;
+			builder = builder.add_root_certificate(certificate);
+		}
+
+		Ok(builder)
+	}
+}
@@ -0,0 +1,459 @@
+use std::{collections::HashMap, fs::{self, File, OpenOptions}, io::{self, Read, Write}, path::{Path, PathBuf}, sync::Mutex};
+
+use chrono::{NaiveDateTime, Utc};
+use flate2::{Compression, read::{MultiGzDecoder, GzDecoder, ZlibDecoder}, write::GzEncoder};
+use rand::Rng;
+use reqwest::{Url, header::{HeaderMap, HOST, TRANSFER_ENCODING}};
+
+use super::{RawResponse, RetrieverOptions, archive::{content_hash, header_value}};
+
+/// Appends every fetched request and response to a WARC file, compressing every record as its own gzip member when
+/// the file name ends in .gz like web archive tools expect. The file is only created once the first page is fetched.
+pub struct WarcWriter {
+	path: PathBuf,
+	file: Mutex<Option<File>>,
+}
+
+/// One WARC record, its block is written as given
+struct Record {
+	kind: &'static str,
+	id: String,
+	date: NaiveDateTime,
+	target_uri: Option<String>,
+	concurrent_to: Option<String>,
+	/// sha256 of the http body in the block
+	payload_digest: Option<String>,
+	content_type: &'static str,
+	block: Vec<u8>,
+}
+
+impl WarcWriter {
+	pub fn new(path: PathBuf) -> WarcWriter {
+		WarcWriter { path, file: Mutex::new(None) }
+	}
+
+	/// Writes the response and the request that fetched it
+	pub fn write(&self, options: &RetrieverOptions, response: &RawResponse) -> io::Result<()> {
+		let mut file = self.file.lock().unwrap();
+
+		if file.is_none() {
+			let mut opened = OpenOptions::new().create(true).append(true).open(&self.path)?;
+
+			// A new file starts with a warcinfo record describing what wrote it
+			if opened.metadata()?.len() == 0 {
+				self.append(&mut opened, &Record {
+					kind: "warcinfo",
+					id: record_id(),
+					date: Utc::now().naive_utc(),
+					target_uri: None,
+					concurrent_to: None,
+					payload_digest: None,
+					content_type: "application/warc-fields",
+					block: format!("software: breach-tracker/{}\r\nformat: WARC File Format 1.1\r\n", env!("CARGO_PKG_VERSION")).into_bytes(),
+				})?;
+			}
+
+			*file = Some(opened);
+		}
+
+		let file = file.as_mut().unwrap();
+		let response_id = record_id();
+
+		self.append(file, &Record {
+			kind: "response",
+			id: response_id.clone(),
+			date: response.fetched_at,
+			target_uri: Some(response.url.clone()),
+			concurrent_to: None,
+			payload_digest: None,
+			content_type: "application/http;msgtype=response",
+			block: http_response(response),
+		})?;
+
+		self.append(file, &Record {
+			kind: "request",
+			id: record_id(),
+			date: response.fetched_at,
+			target_uri: Some(response.url.clone()),
+			concurrent_to: Some(response_id),
+			payload_digest: Some(content_hash(&response.request_body)),
+			content_type: "application/http;msgtype=request",
+			block: http_request(options, response),
+		})?;
+
+		file.flush()
+	}
+
+	fn append(&self, file: &mut File, record: &Record) -> io::Result<()> {
+		let mut bytes = format!("WARC/1.1\r\nWARC-Type: {}\r\nWARC-Record-ID: {}\r\nWARC-Date: {}\r\n", record.kind, record.id, record.date.format("%Y-%m-%dT%H:%M:%SZ"));
+		if let Some(target_uri) = &record.target_uri {
+			bytes.push_str(&format!("WARC-Target-URI: {}\r\n", target_uri));
+		}
+		if let Some(concurrent_to) = &record.concurrent_to {
+			bytes.push_str(&format!("WARC-Concurrent-To: {}\r\n", concurrent_to));
+		}
+		if let Some(payload_digest) = &record.payload_digest {
+			bytes.push_str(&format!("WARC-Payload-Digest: sha256:{}\r\n", payload_digest));
+		}
+		bytes.push_str(&format!("Content-Type: {}\r\nContent-Length: {}\r\n\r\n", record.content_type, record.block.len()));
+
+		let mut bytes = bytes.into_bytes();
+		bytes.extend_from_slice(&record.block);
+		bytes.extend_from_slice(b"\r\n\r\n");
+
+		if self.path.extension().is_some_and(|extension| extension == "gz") {
+			let mut encoder = GzEncoder::new(vec!(), Compression::default());
+			encoder.write_all(&bytes)?;
+			bytes = encoder.finish()?;
+		}
+
+		file.write_all(&bytes)
+	}
+}
+
+fn record_id() -> String {
+	let mut bytes: [u8; 16] = rand::thread_rng().gen();
+	// Random (version 4) uuid
+	bytes[6] = (bytes[6] & 0x0f) | 0x40;
+	bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+	let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+	format!("<urn:uuid:{}-{}-{}-{}-{}>", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+fn http_request(options: &RetrieverOptions, response: &RawResponse) -> Vec<u8> {
+	let url = Url::parse(&response.url).ok();
+	let target = url.as_ref().map(|url| match url.query() {
+		Some(query) => format!("{}?{}", url.path(), query),
+		None => url.path().to_string(),
+	}).unwrap_or_else(|| response.url.clone());
+
+	let method = options.request_type.method();
+
+	// Requests go out as HTTP/1.1 unless the server upgrades, web archive tools record them as such either way
+	let mut head = format!("{} {} HTTP/1.1\r\n", method, target);
+	if let Some(url) = url.as_ref().filter(|_| !response.request_headers.contains_key(HOST)) {
+		match (url.host_str(), url.port()) {
+			(Some(host), Some(port)) => head.push_str(&format!("Host: {}:{}\r\n", host, port)),
+			(Some(host), None) => head.push_str(&format!("Host: {}\r\n", host)),
+			_ => {},
+		}
+	}
+	head.push_str(&header_lines(&response.request_headers));
+	head.push_str("\r\n");
+
+	let mut block = head.into_bytes();
+	block.extend_from_slice(&response.request_body);
+
+	block
+}
+
+fn http_response(response: &RawResponse) -> Vec<u8> {
+	// The body is stored as it was received after transfer decoding, so the transfer encoding no longer applies
+	let mut headers = response.headers.clone();
+	headers.remove(TRANSFER_ENCODING);
+
+	let mut block = format!("{:?} {}\r\n{}\r\n", response.version, response.status, header_lines(&headers)).into_bytes();
+	block.extend_from_slice(&response.body);
+
+	block
+}
+
+fn header_lines(headers: &HeaderMap) -> String {
+	headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, header_value(name, value))).collect()
+}
+
+/// A response read back from a WARC file, its body already transfer and content decoded
+#[derive(Debug)]
+pub struct WarcResponse {
+	/// Normalized target uri
+	pub uri: String,
+	/// Method and sha256 of the body of the request that fetched it, a GET without a body when the file holds no request
+	/// record for it
+	pub method: String,
+	pub request_body_hash: String,
+	pub body: Vec<u8>,
+	pub content_type: Option<String>,
+}
+
+/// The successful http responses of a WARC file in the order they were captured, compressed or not, each with the
+/// request it was concurrent to
+pub fn read_responses(path: &Path) -> io::Result<Vec<WarcResponse>> {
+	let mut data = fs::read(path)?;
+	if data.starts_with(&[0x1f, 0x8b]) {
+		let mut decoded = vec!();
+		MultiGzDecoder::new(&data[..]).read_to_end(&mut decoded)?;
+		data = decoded;
+	}
+
+	let mut responses: Vec<(Option<String>, Option<String>, WarcResponse)> = vec!();
+	// Method and body hash of the requests, by their own record id and the record id they are concurrent to
+	let mut requests: HashMap<String, (String, String)> = HashMap::new();
+	let mut rest = &data[..];
+
+	loop {
+		while let Some(stripped) = rest.strip_prefix(b"\r\n") {
+			rest = stripped;
+		}
+		if rest.is_empty() {
+			break;
+		}
+
+		let (head, after) = split_head(rest).ok_or_else(|| invalid("truncated WARC record header"))?;
+		let mut lines = head.split("\r\n");
+		if !lines.next().is_some_and(|version| version.starts_with("WARC/")) {
+			return Err(invalid("WARC record does not start with a WARC version"));
+		}
+		let fields = header_fields(lines);
+
+		let length: usize = fields.get("content-length").and_then(|length| length.parse().ok())
+			.ok_or_else(|| invalid("WARC record without a Content-Length"))?;
+		if after.len() < length {
+			return Err(invalid("truncated WARC record"));
+		}
+		let (block, after) = after.split_at(length);
+		rest = after;
+
+		if !fields.get("content-type").is_some_and(|content_type| content_type.starts_with("application/http")) {
+			continue;
+		}
+		let id = fields.get("warc-record-id").cloned();
+		let concurrent_to = fields.get("warc-concurrent-to").cloned();
+
+		match fields.get("warc-type").map(String::as_str) {
+			Some("response") => {
+				let Some(target_uri) = fields.get("warc-target-uri") else { continue };
+				let uri = normalize_uri(target_uri.trim_start_matches('<').trim_end_matches('>'));
+
+				if let Some((body, content_type)) = parse_http_response(block)? {
+					let response = WarcResponse { uri, method: "GET".to_string(), request_body_hash: content_hash(&[]), body, content_type };
+					responses.push((id, concurrent_to, response));
+				}
+			},
+			Some("request") => {
+				let (head, body) = split_head(block).ok_or_else(|| invalid("truncated http request in WARC record"))?;
+				let method = head.split_whitespace().next().unwrap_or("GET").to_uppercase();
+				let body_hash = match fields.get("warc-payload-digest").and_then(|digest| digest.strip_prefix("sha256:")) {
+					Some(digest) => digest.to_lowercase(),
+					None => content_hash(body),
+				};
+
+				for id in id.into_iter().chain(concurrent_to) {
+					requests.insert(id, (method.clone(), body_hash.clone()));
+				}
+			},
+			_ => {},
+		}
+	}
+
+	// Requests are written after their response here, other tools write them before it and point the response at them
+	Ok(responses.into_iter().map(|(id, concurrent_to, mut response)| {
+		if let Some((method, body_hash)) = id.into_iter().chain(concurrent_to).find_map(|id| requests.get(&id)) {
+			response.method = method.clone();
+			response.request_body_hash = body_hash.clone();
+		}
+
+		response
+	}).collect())
+}
+
+/// Uris compare the way `Url` serializes them, so captures made by other tools match the urls sources generate
+pub fn normalize_uri(uri: &str) -> String {
+	Url::parse(uri).map(|url| url.to_string()).unwrap_or_else(|_| uri.to_string())
+}
+
+/// Body and content type of a successful http response, none for any other status
+fn parse_http_response(block: &[u8]) -> io::Result<Option<(Vec<u8>, Option<String>)>> {
+	let (head, body) = split_head(block).ok_or_else(|| invalid("truncated http response in WARC record"))?;
+	let mut lines = head.split("\r\n");
+
+	let status = lines.next().and_then(|line| line.split_whitespace().nth(1)).and_then(|status| status.parse::<u16>().ok())
+		.ok_or_else(|| invalid("http response in WARC record without a status"))?;
+	if !(200..300).contains(&status) {
+		return Ok(None);
+	}
+
+	let fields = header_fields(lines);
+
+	let body = match fields.get("transfer-encoding") {
+		Some(encoding) if encoding.eq_ignore_ascii_case("chunked") => dechunk(body)?,
+		_ => body.to_vec(),
+	};
+
+	let mut decoded = vec!();
+	let body = match fields.get("content-encoding").map(|encoding| encoding.to_lowercase()).as_deref() {
+		Some("gzip") | Some("x-gzip") => { GzDecoder::new(&body[..]).read_to_end(&mut decoded)?; decoded },
+		Some("deflate") => { ZlibDecoder::new(&body[..]).read_to_end(&mut decoded)?; decoded },
+		_ => body,
+	};
+
+	Ok(Some((body, fields.get("content-type").cloned())))
+}
+
+/// Header block up to the empty line and everything after it
+fn split_head(data: &[u8]) -> Option<(String, &[u8])> {
+	let end = data.windows(4).position(|window| window == b"\r\n\r\n")?;
+
+	Some((String::from_utf8_lossy(&data[..end]).into_owned(), &data[end + 4..]))
+}
+
+/// Header fields by lower case name, repeated fields joined by commas
+fn header_fields<'a>(lines: impl Iterator<Item = &'a str>) -> HashMap<String, String> {
+	let mut fields: HashMap<String, String> = HashMap::new();
+
+	for line in lines {
+		let Some((name, value)) = line.split_once(':') else { continue };
+
+		fields.entry(name.trim().to_lowercase())
+			.and_modify(|values| { values.push_str(", "); values.push_str(value.trim()); })
+			.or_insert_with(|| value.trim().to_string());
+	}
+
+	fields
+}
+
+fn dechunk(mut data: &[u8]) -> io::Result<Vec<u8>> {
+	let mut body = vec!();
+
+	loop {
+		let end = data.windows(2).position(|window| window == b"\r\n").ok_or_else(|| invalid("truncated chunk size"))?;
+		let size = String::from_utf8_lossy(&data[..end]);
+		let size = usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16).map_err(|_| invalid("invalid chunk size"))?;
+		data = &data[end + 2..];
+
+		if size == 0 {
+			return Ok(body);
+		}
+		if data.len() < size {
+			return Err(invalid("truncated chunk"));
+		}
+
+		body.extend_from_slice(&data[..size]);
+		data = data[size..].strip_prefix(b"\r\n").unwrap_or(&data[size..]);
+	}
+}
+
+fn invalid(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A WARC file in the temp directory, removed once the test is done with it
+	struct TempWarc(PathBuf);
+
+	impl TempWarc {
+		fn new(name: &str) -> TempWarc {
+			TempWarc(std::env::temp_dir().join(format!("breach-tracker-{}-{}", std::process::id(), name)))
+		}
+	}
+
+	impl Drop for TempWarc {
+		fn drop(&mut self) {
+			_ = fs::remove_file(&self.0);
+		}
+	}
+
+	fn response(id: &str, uri: &str, block: &str) -> Record {
+		Record {
+			kind: "response",
+			id: id.to_string(),
+			date: Utc::now().naive_utc(),
+			target_uri: Some(uri.to_string()),
+			concurrent_to: None,
+			payload_digest: None,
+			content_type: "application/http;msgtype=response",
+			block: block.as_bytes().to_vec(),
+		}
+	}
+
+	fn request(concurrent_to: &str, uri: &str, block: &str, payload_digest: Option<String>) -> Record {
+		Record {
+			kind: "request",
+			id: record_id(),
+			date: Utc::now().naive_utc(),
+			target_uri: Some(uri.to_string()),
+			concurrent_to: Some(concurrent_to.to_string()),
+			payload_digest,
+			content_type: "application/http;msgtype=request",
+			block: block.as_bytes().to_vec(),
+		}
+	}
+
+	fn round_trip(name: &str) {
+		let warc = TempWarc::new(name);
+		let writer = WarcWriter::new(warc.0.clone());
+		let mut file = OpenOptions::new().create(true).append(true).open(&warc.0).unwrap();
+		let uri = "http://example.gov/list";
+
+		for record in [
+			response("<urn:uuid:1>", uri, "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\nfirst page"),
+			request("<urn:uuid:1>", uri, "GET /list HTTP/1.1\r\nHost: example.gov\r\n\r\n", Some(content_hash(b""))),
+			response("<urn:uuid:2>", uri, "HTTP/1.1 200 OK\r\n\r\nsecond page"),
+			request("<urn:uuid:2>", uri, "POST /list HTTP/1.1\r\n\r\npage=2", Some(content_hash(b"page=2"))),
+			// Without a digest the request body is hashed as it was recorded
+			response("<urn:uuid:3>", uri, "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nthird\r\n5\r\n page\r\n0\r\n\r\n"),
+			request("<urn:uuid:3>", uri, "POST /list HTTP/1.1\r\n\r\npage=3", None),
+			response("<urn:uuid:4>", "http://example.gov/missing", "HTTP/1.1 404 Not Found\r\n\r\ngone"),
+			response("<urn:uuid:5>", "<http://example.gov/other>", "HTTP/1.1 200 OK\r\n\r\nno request"),
+		] {
+			writer.append(&mut file, &record).unwrap();
+		}
+
+		let responses = read_responses(&warc.0).unwrap();
+		let found: Vec<(&str, &str, &str, &[u8])> = responses.iter()
+			.map(|response| (response.uri.as_str(), response.method.as_str(), response.request_body_hash.as_str(), response.body.as_slice()))
+			.collect();
+		let (empty, second, third) = (content_hash(b""), content_hash(b"page=2"), content_hash(b"page=3"));
+
+		assert_eq!(found, vec!(
+			(uri, "GET", empty.as_str(), &b"first page"[..]),
+			(uri, "POST", second.as_str(), &b"second page"[..]),
+			(uri, "POST", third.as_str(), &b"third page"[..]),
+			("http://example.gov/other", "GET", empty.as_str(), &b"no request"[..]),
+		));
+		assert_eq!(responses[0].content_type.as_deref(), Some("text/html"));
+	}
+
+	#[test]
+	fn reads_back_what_it_appends() {
+		round_trip("round-trip.warc");
+	}
+
+	#[test]
+	fn reads_back_what_it_appends_compressed() {
+		round_trip("round-trip.warc.gz");
+	}
+
+	#[test]
+	fn pairs_requests_written_before_their_response() {
+		let warc = TempWarc::new("request-first.warc");
+		let writer = WarcWriter::new(warc.0.clone());
+		let mut file = OpenOptions::new().create(true).append(true).open(&warc.0).unwrap();
+		let uri = "http://example.gov/list";
+
+		let mut first = request("<urn:uuid:9>", uri, "POST /list HTTP/1.1\r\n\r\npage=1", None);
+		first.id = "<urn:uuid:8>".to_string();
+		first.concurrent_to = None;
+		let mut then = response("<urn:uuid:9>", uri, "HTTP/1.1 200 OK\r\n\r\nposted");
+		then.concurrent_to = Some("<urn:uuid:8>".to_string());
+
+		writer.append(&mut file, &first).unwrap();
+		writer.append(&mut file, &then).unwrap();
+
+		let responses = read_responses(&warc.0).unwrap();
+		assert_eq!(responses.len(), 1);
+		assert_eq!(responses[0].method, "POST");
+		assert_eq!(responses[0].request_body_hash, content_hash(b"page=1"));
+	}
+
+	#[test]
+	fn dechunks_bodies() {
+		assert_eq!(dechunk(b"4\r\nWiki\r\n6;ext=1\r\npedia \r\nE\r\nin \r\n\r\nchunks.\r\n0\r\n\r\n").unwrap(), b"Wikipedia in \r\n\r\nchunks.");
+		assert_eq!(dechunk(b"0\r\n\r\n").unwrap(), b"");
+		assert!(dechunk(b"a\r\nshort\r\n").is_err());
+		assert!(dechunk(b"zz\r\nbody\r\n0\r\n\r\n").is_err());
+	}
+}
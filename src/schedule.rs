@@ -0,0 +1,92 @@
+use std::{str::FromStr, time::Duration};
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::Deserialize;
+
+/// `[source.schedule]` entry, either a cron expression or an interval, optionally delayed by a random jitter
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawSchedule {
+	cron: Option<String>,
+	interval: Option<String>,
+	jitter: Option<String>,
+}
+
+/// When the daemon runs a source
+#[derive(Debug, Clone)]
+pub struct Schedule {
+	pub when: When,
+	/// Upper bound of the random delay added to every run, so sources on the same schedule do not all start at once
+	pub jitter: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub enum When {
+	Cron(Box<cron::Schedule>),
+	Interval(Duration),
+}
+
+impl Schedule {
+	pub fn parse(raw: &RawSchedule) -> Result<Schedule, String> {
+		let when = match (&raw.cron, &raw.interval) {
+			(Some(expression), None) => When::Cron(Box::new(parse_cron(expression)?)),
+			(None, Some(interval)) => {
+				let interval = humantime::parse_duration(interval).map_err(|err| format!("invalid interval {}: {}", interval, err))?;
+				if interval.is_zero() {
+					return Err("schedule interval must be positive".to_string());
+				}
+
+				When::Interval(interval)
+			},
+			(Some(_), Some(_)) => return Err("schedule has both a cron expression and an interval".to_string()),
+			(None, None) => return Err("schedule needs either a cron expression or an interval".to_string()),
+		};
+
+		let jitter = match &raw.jitter {
+			Some(jitter) => humantime::parse_duration(jitter).map_err(|err| format!("invalid jitter {}: {}", jitter, err))?,
+			None => Duration::ZERO,
+		};
+
+		Ok(Schedule { when, jitter })
+	}
+
+	/// Next time the source is due, none when its cron expression has no time after now. An interval counts from the
+	/// previous run, a source that never ran is due right away.
+	pub fn next_run(&self, now: DateTime<Utc>, previous_run: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+		let next = match &self.when {
+			When::Cron(schedule) => schedule.after(&now).next()?,
+			When::Interval(interval) => match previous_run {
+				Some(previous_run) => (previous_run + chrono::Duration::from_std(*interval).unwrap_or(chrono::Duration::MAX)).max(now),
+				None => now,
+			},
+		};
+
+		Some(next + self.random_jitter())
+	}
+
+	fn random_jitter(&self) -> chrono::Duration {
+		if self.jitter.is_zero() {
+			return chrono::Duration::zero();
+		}
+
+		let millis = rand::thread_rng().gen_range(0..=self.jitter.as_millis() as i64);
+		chrono::Duration::milliseconds(millis)
+	}
+}
+
+/// Parses a cron expression, the usual five fields are accepted as well as the six or seven that start with seconds.
+/// Expressions without a time in the future, like one for a year that has passed, are rejected.
+fn parse_cron(expression: &str) -> Result<cron::Schedule, String> {
+	let expression = match expression.split_whitespace().count() {
+		5 => format!("0 {}", expression),
+		_ => expression.to_string(),
+	};
+
+	let schedule = cron::Schedule::from_str(&expression).map_err(|err| format!("invalid cron expression {}: {}", expression, err))?;
+	if schedule.upcoming(Utc).next().is_none() {
+		return Err(format!("cron expression {} has no time in the future", expression));
+	}
+
+	Ok(schedule)
+}
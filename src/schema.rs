@@ -1,5 +1,20 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    backfill_checkpoint (id) {
+        id -> Integer,
+        loc -> Integer,
+        base_url -> Text,
+        page -> Integer,
+        next_url_part -> Nullable<Text>,
+        pages_fetched -> Integer,
+        rows_inserted -> Integer,
+        completed -> Bool,
+        updated_at -> Timestamp,
+        group_name -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     breach_data (id) {
         id -> Integer,
@@ -23,6 +38,33 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    fetched_page (id) {
+        id -> Integer,
+        run_id -> Nullable<Integer>,
+        loc -> Integer,
+        url -> Text,
+        method -> Text,
+        request_headers -> Text,
+        status -> Integer,
+        response_headers -> Text,
+        fetched_at -> Timestamp,
+        content_hash -> Text,
+        size -> Integer,
+        request_body_hash -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    http_cache (loc, url) {
+        loc -> Integer,
+        url -> Text,
+        etag -> Nullable<Text>,
+        last_modified -> Nullable<Text>,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     last_retrieved (id) {
         id -> Integer,
@@ -31,8 +73,60 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    notice_document (id) {
+        id -> Integer,
+        breach_data_id -> Integer,
+        link -> Text,
+        content_hash -> Nullable<Text>,
+        mime_type -> Nullable<Text>,
+        size -> Nullable<Integer>,
+        fetched_at -> Nullable<Timestamp>,
+        status -> Nullable<Integer>,
+        error -> Nullable<Text>,
+        attempts -> Integer,
+        attempted_at -> Timestamp,
+        retry_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    scrape_run (id) {
+        id -> Integer,
+        loc -> Integer,
+        run_status -> Integer,
+        started_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+        pages_fetched -> Integer,
+        rows_parsed -> Integer,
+        rows_inserted -> Integer,
+        classifications_added -> Integer,
+        error_count -> Integer,
+        errors -> Nullable<Text>,
+        watermark -> Nullable<Timestamp>,
+        error_kind -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    source_lock (loc) {
+        loc -> Integer,
+        holder -> Text,
+        acquired_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::joinable!(notice_document -> breach_data (breach_data_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
+    backfill_checkpoint,
     breach_data,
     classification,
+    fetched_page,
+    http_cache,
     last_retrieved,
+    notice_document,
+    scrape_run,
+    source_lock,
 );
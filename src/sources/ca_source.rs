@@ -0,0 +1,26 @@
+use crate::{config::SourceConfig, parsers::{Parser, ca_parser::CaParser}, retrievers::{Retriever, single_page::SinglePage}};
+use super::Source;
+
+pub struct CaSource {
+	config: SourceConfig,
+}
+
+impl CaSource {
+	pub fn new(config: SourceConfig) -> Self {
+		Self { config }
+	}
+}
+
+impl Source for CaSource {
+	fn config(&self) -> &SourceConfig {
+		&self.config
+	}
+
+	fn retriever(&self) -> Box<dyn Retriever> {
+		Box::new(SinglePage{})
+	}
+
+	fn parser(&self) -> Box<dyn Parser + Send> {
+		Box::new(CaParser{})
+	}
+}
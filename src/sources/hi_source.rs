@@ -0,0 +1,26 @@
+use crate::{config::SourceConfig, parsers::{Parser, hi_parser::HiParser}, retrievers::{Retriever, single_page::SinglePage}};
+use super::Source;
+
+pub struct HiSource {
+	config: SourceConfig,
+}
+
+impl HiSource {
+	pub fn new(config: SourceConfig) -> Self {
+		Self { config }
+	}
+}
+
+impl Source for HiSource {
+	fn config(&self) -> &SourceConfig {
+		&self.config
+	}
+
+	fn retriever(&self) -> Box<dyn Retriever> {
+		Box::new(SinglePage{})
+	}
+
+	fn parser(&self) -> Box<dyn Parser + Send> {
+		Box::new(HiParser{})
+	}
+}
@@ -0,0 +1,33 @@
+use chrono::NaiveDate;
+
+use crate::{config::SourceConfig, parsers::{Parser, md_parser::MdParser}, retrievers::{Retriever, multi_page::MultiPage}};
+use super::Source;
+
+pub struct MdSource {
+	config: SourceConfig,
+}
+
+impl MdSource {
+	pub fn new(config: SourceConfig) -> Self {
+		Self { config }
+	}
+}
+
+impl Source for MdSource {
+	fn config(&self) -> &SourceConfig {
+		&self.config
+	}
+
+	fn retriever(&self) -> Box<dyn Retriever> {
+		Box::new(MultiPage::new(self.config.session.clone()))
+	}
+
+	fn parser(&self) -> Box<dyn Parser + Send> {
+		Box::new(MdParser{})
+	}
+
+	// Notices without a received date are stored as year 1, so they are only collected on the first run
+	fn initial_collect_until(&self) -> NaiveDate {
+		NaiveDate::from_ymd_opt(-1, 1, 1).unwrap()
+	}
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::SqliteConnection;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, USER_AGENT};
+
+use crate::{dto::State, config::{Config, SourceConfig}, data::get_last_retrieved, error::{Error, Result}, parsers::Parser, retrievers::{Retriever, RetrieverOptions, PageCursor, WebRequestType, pagination::Pagination, sharepoint::SharePointList}};
+
+pub mod wa_source;
+pub mod or_source;
+pub mod ca_source;
+pub mod md_source;
+pub mod hi_source;
+
+/// Everything needed to scrape one jurisdiction. The retriever and parser are chosen here, urls, headers and
+/// pagination come from the source's entry in the sources file. New states implement this and are added to `create`.
+pub trait Source: Send + Sync {
+	fn config(&self) -> &SourceConfig;
+
+	fn retriever(&self) -> Box<dyn Retriever>;
+
+	fn parser(&self) -> Box<dyn Parser + Send>;
+
+	fn state(&self) -> State {
+		self.config().state
+	}
+
+	/// Retriever the source's entry in the sources file asks for, a SharePoint list when it has `[source.sharepoint]`
+	/// settings and the source's own retriever otherwise
+	fn configured_retriever(&self) -> Box<dyn Retriever> {
+		let config = self.config();
+
+		match &config.sharepoint {
+			Some(sharepoint) => Box::new(SharePointList::new(sharepoint.clone(), config.session.clone())),
+			None => self.retriever(),
+		}
+	}
+
+	/// Date to collect back to when the state has never been retrieved
+	fn initial_collect_until(&self) -> NaiveDate {
+		NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
+	}
+
+	/// Pagination of the source's entry in the sources file, page numbers from 0 when it has none
+	fn pagination(&self) -> Pagination {
+		self.config().pagination.clone().unwrap_or_default()
+	}
+
+	/// Builds the options for every configured url of this source
+	fn options(&self, conn: &mut SqliteConnection) -> Result<Vec<RetrieverOptions>> {
+		let incremental = &self.config().incremental;
+		let collect_until = collect_until(conn, self.state(), self.initial_collect_until(), incremental.overlap)?;
+
+		Ok(self.options_until(collect_until, incremental.stop_after_known))
+	}
+
+	/// Builds the options for a backfill, which ignores the last retrieved date and what is stored and collects back to
+	/// the initial date
+	fn backfill_options(&self) -> Vec<RetrieverOptions> {
+		self.options_until(self.initial_collect_until().and_hms_opt(0, 0, 0).unwrap(), None)
+	}
+
+	fn options_until(&self, collect_until: NaiveDateTime, stop_after_known: Option<usize>) -> Vec<RetrieverOptions> {
+		let config = self.config();
+		let pagination = self.pagination();
+
+		config.urls.iter().map(|url| RetrieverOptions {
+			collect_until,
+			stop_after_known,
+			base_url: url.clone(),
+			headers: config.headers.clone(),
+			state: self.state(),
+			request_type: config.request_type.clone(),
+			start: pagination.start(),
+			pagination: pagination.clone(),
+			retry: config.retry.clone(),
+			run_id: None,
+			validators: None,
+		}).collect()
+	}
+
+	/// Options for downloading a document a breach of this source links to. The headers of the listing are left out
+	/// apart from its user agent, they may ask for JSON or carry a session.
+	fn document_options(&self, link: &str) -> RetrieverOptions {
+		let config = self.config();
+
+		let mut headers = default_headers();
+		if let Some(user_agent) = config.headers.get(USER_AGENT) {
+			headers.insert(USER_AGENT, user_agent.clone());
+		}
+
+		RetrieverOptions {
+			collect_until: self.initial_collect_until().and_hms_opt(0, 0, 0).unwrap(),
+			stop_after_known: None,
+			base_url: link.to_string(),
+			headers,
+			state: self.state(),
+			request_type: WebRequestType::Get,
+			start: PageCursor::default(),
+			pagination: Pagination::default(),
+			retry: config.retry.clone(),
+			run_id: None,
+			validators: None,
+		}
+	}
+}
+
+fn create(config: &SourceConfig) -> Box<dyn Source> {
+	let config = config.clone();
+
+	match config.state {
+		State::WA => Box::new(wa_source::WaSource::new(config)),
+		State::OR => Box::new(or_source::OrSource::new(config)),
+		State::CA => Box::new(ca_source::CaSource::new(config)),
+		State::MD => Box::new(md_source::MdSource::new(config)),
+		State::HI => Box::new(hi_source::HiSource::new(config)),
+	}
+}
+
+/// Every enabled source in the config when no states are given, otherwise the sources of the given states
+pub fn registered_for(config: &Config, states: &[State]) -> Result<Vec<Box<dyn Source>>> {
+	if states.is_empty() {
+		return Ok(config.sources.iter().filter(|source| source.enabled).map(create).collect());
+	}
+
+	states.iter().map(|state| match config.source(*state) {
+		Some(source) => Ok(create(source)),
+		None => Err(Error::config(format!("{:?} is not configured in the sources file", state))),
+	}).collect()
+}
+
+/// Headers every source sends, the user agent comes from the `[http]` settings
+pub fn default_headers() -> HeaderMap<HeaderValue> {
+	let mut headers = HeaderMap::new();
+
+	headers.insert(ACCEPT, "*/*".parse().unwrap());
+
+	headers
+}
+
+/// How far an incremental scrape of a source goes back past what is already stored
+#[derive(Debug, Clone)]
+pub struct IncrementalPolicy {
+	/// Breaches are collected back this far before the last retrieved date, so notices posted late are not missed
+	pub overlap: Duration,
+	/// Stop once this many breaches in a row were already stored instead of once a page reaches back past the overlap,
+	/// for states whose listings are not in date order
+	pub stop_after_known: Option<usize>,
+}
+
+impl Default for IncrementalPolicy {
+	fn default() -> Self {
+		Self {
+			overlap: Duration::from_secs(24 * 60 * 60),
+			stop_after_known: None,
+		}
+	}
+}
+
+/// Date to collect breaches until, `overlap` before the last retrieved date
+pub fn collect_until(conn: &mut SqliteConnection, state: State, default: NaiveDate, overlap: Duration) -> Result<NaiveDateTime> {
+	let last_retrieved = get_last_retrieved(conn, state.into()).map_err(Error::storage(state))?;
+	let default = default.and_hms_opt(0, 0, 0).unwrap();
+
+	Ok(match last_retrieved {
+		Some(lr) => chrono::Duration::from_std(overlap).ok()
+			.and_then(|overlap| lr.retrieved_date.checked_sub_signed(overlap))
+			.unwrap_or(default),
+		None => default,
+	})
+}
@@ -0,0 +1,26 @@
+use crate::{config::SourceConfig, parsers::{Parser, or_parser::OrParser}, retrievers::{Retriever, single_page::SinglePage}};
+use super::Source;
+
+pub struct OrSource {
+	config: SourceConfig,
+}
+
+impl OrSource {
+	pub fn new(config: SourceConfig) -> Self {
+		Self { config }
+	}
+}
+
+impl Source for OrSource {
+	fn config(&self) -> &SourceConfig {
+		&self.config
+	}
+
+	fn retriever(&self) -> Box<dyn Retriever> {
+		Box::new(SinglePage{})
+	}
+
+	fn parser(&self) -> Box<dyn Parser + Send> {
+		Box::new(OrParser{})
+	}
+}
@@ -0,0 +1,26 @@
+use crate::{config::SourceConfig, parsers::{Parser, wa_parser::WaParser}, retrievers::{Retriever, multi_page::MultiPage}};
+use super::Source;
+
+pub struct WaSource {
+	config: SourceConfig,
+}
+
+impl WaSource {
+	pub fn new(config: SourceConfig) -> Self {
+		Self { config }
+	}
+}
+
+impl Source for WaSource {
+	fn config(&self) -> &SourceConfig {
+		&self.config
+	}
+
+	fn retriever(&self) -> Box<dyn Retriever> {
+		Box::new(MultiPage::new(self.config.session.clone()))
+	}
+
+	fn parser(&self) -> Box<dyn Parser + Send> {
+		Box::new(WaParser{})
+	}
+}